@@ -0,0 +1,185 @@
+use crate::{BLOCKSIZE, BUFF, Game, GameCondition, SearchPurpose, draw_board};
+use chess::engine::{self, Limits, SearchInfo};
+use chess::notation;
+use chess::piece::Color as Side;
+use chess::zobrist::Hash;
+use macroquad::prelude::*;
+use macroquad::ui::root_ui;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver};
+
+const MAX_LINES: usize = 5;
+const BAR_WIDTH: f32 = 20.0;
+
+// the endless search behind the analysis board
+pub struct Analysis {
+    lines: Vec<SearchInfo>,
+    updates: Option<Receiver<Vec<SearchInfo>>>,
+    // the position the running search is looking at
+    analysed: Option<Hash>,
+    line_count: usize,
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Analysis {
+            lines: Vec::new(),
+            updates: None,
+            analysed: None,
+            line_count: 3,
+        }
+    }
+}
+
+// the share of the bar that is white's, a logistic curve over the score in centipawns
+fn white_share(score: i32) -> f32 {
+    if engine::is_mate_score(score) {
+        if score > 0 { 1.0 } else { 0.0 }
+    } else {
+        1.0 / (1.0 + 10f32.powf(-score as f32 / 400.0))
+    }
+}
+
+impl Game {
+    // scores come from the side to move, the bar and the lines show them from white's side
+    fn white_score(&self, score: i32) -> i32 {
+        match self.board.side_to_move() {
+            Side::White => score,
+            Side::Black => -score,
+        }
+    }
+
+    // any move for either side, with the engine looking at every new position
+    pub fn analysis_screen(&mut self) {
+        if let Some(updates) = &self.analysis.updates {
+            while let Ok(lines) = updates.try_recv() {
+                self.analysis.lines = lines;
+            }
+        }
+        // a hint or computer move still running from the game is of no use here
+        let _ = self.finished_search();
+        if self.analysis.analysed != Some(self.board.hash()) {
+            // lines for the old position can't be shown on the new one
+            self.analysis.lines.clear();
+            self.analysis.updates = None;
+            if self.thinking.is_some() {
+                self.stop.store(true, Ordering::Relaxed);
+            } else {
+                self.start_analysis();
+            }
+        }
+
+        draw_board(&self.board, &self.spritesheet, self.settings.theme, self.is_flipped());
+        self.draw_animation();
+        self.draw_explosion();
+        if self.selected {
+            let (selected_x, selected_y) = self.screen_square(self.selected_x, self.selected_y);
+            draw_rectangle(
+                selected_x as f32 * BLOCKSIZE - BUFF,
+                selected_y as f32 * BLOCKSIZE - BUFF,
+                BLOCKSIZE + 2.0 * BUFF,
+                BLOCKSIZE + 2.0 * BUFF,
+                Color::new(0.0, 0.0, 0.0, 0.2),
+            );
+        }
+        if self.settings.show_legal_moves {
+            self.highlight_legal_moves();
+        }
+        self.highlight_attackers();
+        self.draw_eval_bar();
+        self.draw_lines();
+        self.analysis_buttons();
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (mouse_x, mouse_y) = mouse_position();
+            let (x, y) = ((mouse_x / BLOCKSIZE) as usize, (mouse_y / BLOCKSIZE) as usize);
+            if x < self.board.width && y < self.board.height {
+                let (x, y) = self.screen_square(x, y);
+                self.select_square(x, y);
+            }
+        }
+    }
+
+    fn start_analysis(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let count = self.analysis.line_count;
+        self.analysis.lines.clear();
+        self.analysis.updates = Some(receiver);
+        self.analysis.analysed = Some(self.board.hash());
+        let limits = Limits { time: None, ..Limits::default() };
+        self.start_search(SearchPurpose::Analysis, move |engine, board| {
+            let mut lines = engine.search_lines(board, limits, count, |lines| {
+                let _ = sender.send(lines.to_vec());
+            });
+            lines.swap_remove(0)
+        });
+    }
+
+    // white fills the bar from the bottom, or from the top when the board is flipped
+    fn draw_eval_bar(&self) {
+        let x = self.board.width as f32 * BLOCKSIZE + 5.0;
+        let height = self.board.height as f32 * BLOCKSIZE;
+        let share = self
+            .analysis
+            .lines
+            .first()
+            .filter(|line| !line.pv.is_empty())
+            .map_or(0.5, |line| white_share(self.white_score(line.score)));
+        let white_height = height * share;
+        let y = if self.is_flipped() { 0.0 } else { height - white_height };
+        draw_rectangle(x, 0.0, BAR_WIDTH, height, DARKGRAY);
+        draw_rectangle(x, y, BAR_WIDTH, white_height, WHITE);
+    }
+
+    // each line is its score and then its moves in SAN, four to a row
+    fn draw_lines(&self) {
+        let x = self.board.width as f32 * BLOCKSIZE + BAR_WIDTH + 15.0;
+        let mut y = 30.0;
+        let Some(first) = self.analysis.lines.first() else {
+            draw_text("thinking...", x, y, 18.0, GRAY);
+            return;
+        };
+        draw_text(&format!("depth {}", first.depth), x, y, 18.0, GRAY);
+        for line in &self.analysis.lines {
+            if line.pv.is_empty() {
+                continue;
+            }
+            y += 30.0;
+            let score = engine::format_score(self.white_score(line.score));
+            draw_text(&score, x, y, 18.0, WHITE);
+            let san = notation::line_to_san(&self.board, &line.pv[..line.pv.len().min(8)]);
+            let moves: Vec<&str> = san.split(' ').collect();
+            for row in moves.chunks(4) {
+                y += 18.0;
+                draw_text(&row.join(" "), x, y, 16.0, LIGHTGRAY);
+            }
+        }
+    }
+
+    fn analysis_buttons(&mut self) {
+        let x = self.board.width as f32 * BLOCKSIZE + BAR_WIDTH + 15.0;
+        let y = self.board.height as f32 * BLOCKSIZE - 80.0;
+        if root_ui().button(vec2(x, y), "Take back")
+            && let Some((mv, undo)) = self.moves.pop()
+        {
+            self.board.unmake_move(mv, undo);
+            self.turn = self.board.side_to_move();
+            self.animation = None;
+            self.explosion = None;
+            self.deselect_and_clear_legal_moves();
+        }
+        let lines_text = format!("Lines: {}", self.analysis.line_count);
+        if root_ui().button(vec2(x, y + 25.0), lines_text.as_str()) {
+            self.analysis.line_count = self.analysis.line_count % MAX_LINES + 1;
+            // search the same position again with the new count
+            self.analysis.analysed = None;
+        }
+        if root_ui().button(vec2(x, y - 25.0), "Edit position") {
+            self.start_setup();
+        }
+        if root_ui().button(vec2(x, y + 50.0), "Back") {
+            self.stop.store(true, Ordering::Relaxed);
+            self.game_condition = GameCondition::Restart;
+        }
+    }
+}
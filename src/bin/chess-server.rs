@@ -0,0 +1,520 @@
+// a server for many network games at once, rooms are created and joined by name and anyone
+// can watch; it keeps every room's board and clocks itself, so its word on a game is final
+use chess::board::Board;
+use chess::clock::{Clock, TimeControl};
+use chess::net::{self, Connection, Host, Message, RoomInfo};
+use chess::notation;
+use chess::piece::Color;
+use chess::variant::{self, Variant};
+use std::collections::{BTreeMap, HashMap};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how long the server sleeps between looking at its connections
+const POLL: Duration = Duration::from_millis(10);
+// seats are indexed by colour
+const COLORS: [Color; 2] = [Color::White, Color::Black];
+
+struct Client {
+    connection: Connection,
+    // has said hello with the same protocol version
+    greeted: bool,
+    room: Option<String>,
+}
+
+struct Room {
+    variant: &'static dyn Variant,
+    time_control: TimeControl,
+    start: Board,
+    board: Board,
+    // in coordinate notation, replayed to anyone who starts watching late
+    moves: Vec<String>,
+    clock: Option<Clock>,
+    // client ids, indexed by colour
+    players: [Option<usize>; 2],
+    // what each player resumes with, and since when a player who dropped out has been gone
+    tokens: [String; 2],
+    away: [Option<Instant>; 2],
+    spectators: Vec<usize>,
+    draw_offer: Option<Color>,
+    playing: bool,
+    result: Option<(Option<Color>, String)>,
+}
+
+impl Room {
+    fn new(variant: &'static dyn Variant, time_control: TimeControl, creator: usize) -> Self {
+        let start = Board::start_position(variant);
+        Room {
+            variant,
+            time_control,
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+            clock: None,
+            players: [Some(creator), None],
+            tokens: Default::default(),
+            away: [None; 2],
+            spectators: Vec::new(),
+            draw_offer: None,
+            playing: false,
+            result: None,
+        }
+    }
+
+    fn members(&self) -> Vec<usize> {
+        self.players.iter().flatten().chain(&self.spectators).copied().collect()
+    }
+
+    fn start_message(&self, color: Option<Color>) -> Message {
+        Message::Start {
+            variant: self.variant.name().to_string(),
+            fen: notation::to_fen(&self.start, 1),
+            time_control: self.time_control,
+            color,
+        }
+    }
+
+    fn clock_message(&self) -> Option<Message> {
+        self.clock.as_ref().map(|clock| Message::Clock {
+            white: clock.remaining(Color::White),
+            black: clock.remaining(Color::Black),
+        })
+    }
+
+    // the whole game so far, for someone coming back to it or starting to watch late
+    fn sync_message(&self, color: Option<Color>) -> Message {
+        Message::Sync {
+            variant: self.variant.name().to_string(),
+            fen: notation::to_fen(&self.start, 1),
+            time_control: self.time_control,
+            color,
+            moves: self.moves.clone(),
+            clock: self.clock.as_ref().map(|clock| [clock.remaining(Color::White), clock.remaining(Color::Black)]),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Server {
+    clients: HashMap<usize, Client>,
+    rooms: BTreeMap<String, Room>,
+    next_id: usize,
+}
+
+// sending only queues the message, so a client that stops reading holds up nobody else;
+// one whose queue fills up is cut off and dropped the next time it is polled
+fn send(clients: &mut HashMap<usize, Client>, id: usize, message: &Message) {
+    if let Some(client) = clients.get_mut(&id) {
+        let _ = client.connection.send(message);
+    }
+}
+
+fn send_all(clients: &mut HashMap<usize, Client>, ids: &[usize], message: &Message) {
+    for &id in ids {
+        send(clients, id, message);
+    }
+}
+
+impl Server {
+    fn add(&mut self, connection: Connection) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.clients.insert(id, Client { connection, greeted: false, room: None });
+    }
+
+    fn poll(&mut self) {
+        let ids: Vec<usize> = self.clients.keys().copied().collect();
+        for id in ids {
+            while let Some(client) = self.clients.get(&id) {
+                match client.connection.receive() {
+                    Ok(Some(message)) => self.handle(id, message),
+                    Ok(None) => break,
+                    Err(_) => {
+                        self.leave(id);
+                        self.clients.remove(&id);
+                    }
+                }
+            }
+        }
+    }
+
+    // every room's clock runs for its side to move, flagging ends the game and so does
+    // staying away too long
+    fn tick(&mut self, elapsed: f32) {
+        let mut losers = Vec::new();
+        for (name, room) in self.rooms.iter_mut().filter(|(_, room)| room.playing) {
+            let turn = room.board.side_to_move();
+            if let Some(clock) = &mut room.clock {
+                clock.tick(turn, elapsed);
+                if clock.is_flagged(turn) {
+                    losers.push((name.clone(), turn, "time"));
+                }
+            }
+            for (color, away) in COLORS.into_iter().zip(room.away) {
+                if away.is_some_and(|since| since.elapsed().as_secs_f32() >= net::ABANDON_SECONDS) {
+                    losers.push((name.clone(), color, "abandoned"));
+                }
+            }
+        }
+        for (name, loser, reason) in losers {
+            self.finish(&name, Some(loser.opposite()), reason);
+            self.close_if_empty(&name);
+        }
+    }
+
+    fn rooms_message(&self) -> Message {
+        let rooms = self
+            .rooms
+            .iter()
+            .map(|(name, room)| RoomInfo {
+                name: name.clone(),
+                variant: room.variant.name().to_string(),
+                time_control: room.time_control,
+                players: room.players.iter().flatten().count(),
+                spectators: room.spectators.len(),
+            })
+            .collect();
+        Message::Rooms { rooms }
+    }
+
+    // everyone still choosing a room sees the list change
+    fn broadcast_rooms(&mut self) {
+        let message = self.rooms_message();
+        let ids: Vec<usize> = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.greeted && client.room.is_none())
+            .map(|(&id, _)| id)
+            .collect();
+        send_all(&mut self.clients, &ids, &message);
+    }
+
+    fn error(&mut self, id: usize, text: impl Into<String>) {
+        send(&mut self.clients, id, &Message::Error { text: text.into() });
+    }
+
+    // the room a client plays in and its colour there
+    fn seat(&self, id: usize) -> Option<(String, Color)> {
+        let name = self.clients.get(&id)?.room.clone()?;
+        let room = self.rooms.get(&name)?;
+        let index = room.players.iter().position(|&player| player == Some(id))?;
+        Some((name, COLORS[index]))
+    }
+
+    fn finish(&mut self, name: &str, winner: Option<Color>, reason: &str) {
+        let Some(room) = self.rooms.get_mut(name) else {
+            return;
+        };
+        if !room.playing {
+            return;
+        }
+        room.playing = false;
+        room.away = [None; 2];
+        room.result = Some((winner, reason.to_string()));
+        let members = room.members();
+        let end = Message::End { winner, reason: reason.to_string() };
+        send_all(&mut self.clients, &members, &end);
+    }
+
+    fn handle(&mut self, id: usize, message: Message) {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return;
+        };
+        if let Message::Hello { version } = message {
+            match net::check_version(version) {
+                Ok(()) => {
+                    client.greeted = true;
+                    send(&mut self.clients, id, &Message::Hello { version: net::PROTOCOL_VERSION });
+                    let rooms = self.rooms_message();
+                    send(&mut self.clients, id, &rooms);
+                }
+                Err(text) => {
+                    self.error(id, text);
+                    self.clients.remove(&id);
+                }
+            }
+            return;
+        }
+        if !client.greeted {
+            return;
+        }
+        let in_room = client.room.is_some();
+        match message {
+            Message::ListRooms => {
+                let rooms = self.rooms_message();
+                send(&mut self.clients, id, &rooms);
+            }
+            Message::CreateRoom { .. }
+            | Message::JoinRoom { .. }
+            | Message::WatchRoom { .. }
+            | Message::Resume { .. }
+                if in_room =>
+            {
+                self.error(id, "you are already in a room");
+            }
+            Message::CreateRoom { name, variant, time_control } => self.create(id, name.trim(), &variant, time_control),
+            Message::JoinRoom { name } => self.join(id, &name),
+            Message::WatchRoom { name } => self.watch(id, &name),
+            Message::Resume { token } => self.resume(id, &token),
+            Message::Move { mv } => self.play(id, &mv),
+            Message::Resign => {
+                if let Some((name, color)) = self.seat(id) {
+                    self.finish(&name, Some(color.opposite()), "resignation");
+                }
+            }
+            Message::DrawOffer | Message::DrawDecline => {
+                let Some((name, color)) = self.seat(id) else {
+                    return;
+                };
+                let Some(room) = self.rooms.get_mut(&name).filter(|room| room.playing) else {
+                    return;
+                };
+                room.draw_offer = matches!(message, Message::DrawOffer).then_some(color);
+                if let Some(opponent) = room.players[color.opposite() as usize] {
+                    send(&mut self.clients, opponent, &message);
+                }
+            }
+            Message::DrawAccept => {
+                if let Some((name, color)) = self.seat(id)
+                    && self.rooms.get(&name).is_some_and(|room| room.draw_offer == Some(color.opposite()))
+                {
+                    self.finish(&name, None, "agreement");
+                }
+            }
+            // passed on to the rest of the room, saying who it is from
+            Message::Chat { text, .. } => {
+                let from = self.seat(id).map(|(_, color)| color);
+                let Some(room) = self.clients.get(&id).and_then(|client| client.room.as_ref()) else {
+                    return;
+                };
+                let Some(room) = self.rooms.get(room) else {
+                    return;
+                };
+                let others: Vec<usize> = room.members().into_iter().filter(|&member| member != id).collect();
+                send_all(&mut self.clients, &others, &Message::Chat { text, from });
+            }
+            // the server keeps its own clocks and says how games end
+            Message::Hello { .. }
+            | Message::Start { .. }
+            | Message::Clock { .. }
+            | Message::Seat { .. }
+            | Message::Sync { .. }
+            | Message::Disconnected { .. }
+            | Message::Reconnected { .. }
+            | Message::Rooms { .. }
+            | Message::End { .. }
+            | Message::Error { .. } => {}
+        }
+    }
+
+    fn create(&mut self, id: usize, name: &str, variant: &str, time_control: TimeControl) {
+        let Some(variant) = variant::from_name(variant) else {
+            return self.error(id, format!("unknown variant {}", variant));
+        };
+        if name.is_empty() {
+            return self.error(id, "a room needs a name");
+        }
+        if self.rooms.contains_key(name) {
+            return self.error(id, format!("there is already a room called {}", name));
+        }
+        self.rooms.insert(name.to_string(), Room::new(variant, time_control, id));
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(name.to_string());
+        }
+        self.broadcast_rooms();
+    }
+
+    // the second player to arrive plays Black and starts the game
+    fn join(&mut self, id: usize, name: &str) {
+        let Some(room) = self.rooms.get_mut(name) else {
+            return self.error(id, format!("there is no room called {}", name));
+        };
+        let Some(creator) = room.players[0].filter(|_| room.players[1].is_none() && room.tokens[1].is_empty()) else {
+            return self.error(id, format!("{} already has two players", name));
+        };
+        room.players[1] = Some(id);
+        room.tokens = [net::new_token(), net::new_token()];
+        let seats = room.tokens.clone().map(|token| Message::Seat { token });
+        room.playing = true;
+        room.clock = Clock::new(room.time_control);
+        let (white, black) = (room.start_message(Some(Color::White)), room.start_message(Some(Color::Black)));
+        let watching = room.start_message(None);
+        let spectators = room.spectators.clone();
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(name.to_string());
+        }
+        send(&mut self.clients, creator, &white);
+        send(&mut self.clients, id, &black);
+        send(&mut self.clients, creator, &seats[0]);
+        send(&mut self.clients, id, &seats[1]);
+        // anyone who was already watching starts again from the first move
+        send_all(&mut self.clients, &spectators, &watching);
+        self.broadcast_rooms();
+    }
+
+    fn watch(&mut self, id: usize, name: &str) {
+        let Some(room) = self.rooms.get_mut(name) else {
+            return self.error(id, format!("there is no room called {}", name));
+        };
+        room.spectators.push(id);
+        let mut catch_up = vec![room.sync_message(None)];
+        if let Some((winner, reason)) = &room.result {
+            catch_up.push(Message::End { winner: *winner, reason: reason.clone() });
+        }
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(name.to_string());
+        }
+        for message in &catch_up {
+            send(&mut self.clients, id, message);
+        }
+        self.broadcast_rooms();
+    }
+
+    fn play(&mut self, id: usize, text: &str) {
+        let Some((name, color)) = self.seat(id) else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&name).filter(|room| room.playing) else {
+            return;
+        };
+        let mv = match net::read_move(&mut room.board, color, text) {
+            Ok(mv) => mv,
+            Err(text) => return self.error(id, text),
+        };
+        room.board.make_move(mv);
+        let mv = notation::to_coordinates(&room.board, mv);
+        room.moves.push(mv.clone());
+        room.draw_offer = None;
+        if let Some(clock) = &mut room.clock {
+            clock.press(color);
+        }
+        let others: Vec<usize> = room.members().into_iter().filter(|&member| member != id).collect();
+        let members = room.members();
+        let clock = room.clock_message();
+        let end = room.board.game_end();
+        send_all(&mut self.clients, &others, &Message::Move { mv });
+        if let Some(clock) = clock {
+            send_all(&mut self.clients, &members, &clock);
+        }
+        if let Some(end) = end {
+            self.finish(&name, end.winner, end.reason);
+        }
+    }
+
+    // a player dropping out of a game that's going on gets a while to come back, a room is
+    // closed once both players have gone
+    fn leave(&mut self, id: usize) {
+        let Some(name) = self.clients.get(&id).and_then(|client| client.room.clone()) else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&name) else {
+            return;
+        };
+        room.spectators.retain(|&spectator| spectator != id);
+        if let Some(index) = room.players.iter().position(|&player| player == Some(id)) {
+            room.players[index] = None;
+            if room.playing {
+                room.away[index] = Some(Instant::now());
+                let members = room.members();
+                let away = Message::Disconnected { color: COLORS[index], seconds: net::ABANDON_SECONDS };
+                send_all(&mut self.clients, &members, &away);
+            }
+        }
+        self.close_if_empty(&name);
+        self.broadcast_rooms();
+    }
+
+    fn close_if_empty(&mut self, name: &str) {
+        let Some(room) = self.rooms.get(name) else {
+            return;
+        };
+        if room.playing || room.players.iter().any(Option::is_some) {
+            return;
+        }
+        for spectator in self.rooms.remove(name).map(|room| room.spectators).unwrap_or_default() {
+            if let Some(client) = self.clients.get_mut(&spectator) {
+                client.room = None;
+            }
+            send(&mut self.clients, spectator, &Message::Error { text: format!("{} has closed", name) });
+        }
+    }
+
+    // a player back on a new connection takes their seat again and is sent the game so far,
+    // along with how it ended if that was decided while they were gone; the old connection
+    // is often still hanging on when they do, and the token is enough to hang it up
+    fn resume(&mut self, id: usize, token: &str) {
+        let seat = self.rooms.iter().find_map(|(name, room)| {
+            let index = room.tokens.iter().position(|seat| !seat.is_empty() && seat == token)?;
+            Some((name.clone(), index))
+        });
+        let Some((name, index)) = seat else {
+            return self.error(id, "there is no game to go back to");
+        };
+        if let Some(stale) = self.rooms[&name].players[index].filter(|&stale| stale != id) {
+            self.clients.remove(&stale);
+        }
+        let Some(room) = self.rooms.get_mut(&name) else {
+            return;
+        };
+        room.players[index] = Some(id);
+        room.away[index] = None;
+        let mut catch_up = vec![room.sync_message(Some(COLORS[index]))];
+        if let Some((winner, reason)) = &room.result {
+            catch_up.push(Message::End { winner: *winner, reason: reason.clone() });
+        }
+        let others: Vec<usize> = room.members().into_iter().filter(|&member| member != id).collect();
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.room = Some(name);
+        }
+        for message in &catch_up {
+            send(&mut self.clients, id, message);
+        }
+        send_all(&mut self.clients, &others, &Message::Reconnected { color: COLORS[index] });
+        self.broadcast_rooms();
+    }
+}
+
+fn main() {
+    // `--port <number>` listens somewhere other than the default port
+    let mut port = net::DEFAULT_PORT;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--port" {
+            match args.next().and_then(|port| port.parse().ok()) {
+                Some(number) => port = number,
+                None => {
+                    eprintln!("--port needs a port number");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    let host = match Host::bind(port) {
+        Ok(host) => host,
+        Err(error) => {
+            eprintln!("can't listen on port {}: {}", port, error);
+            std::process::exit(1);
+        }
+    };
+    println!("listening on port {}", port);
+
+    let mut server = Server::default();
+    let mut last = Instant::now();
+    loop {
+        loop {
+            match host.accept() {
+                Ok(Some(connection)) => server.add(connection),
+                Ok(None) => break,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    break;
+                }
+            }
+        }
+        server.poll();
+        let now = Instant::now();
+        server.tick((now - last).as_secs_f32());
+        last = now;
+        thread::sleep(POLL);
+    }
+}
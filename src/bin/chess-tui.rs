@@ -0,0 +1,207 @@
+// terminal frontend for playing where no window can be opened, e.g. over ssh
+use chess::board::{self, Board, Move};
+use chess::engine::{self, Engine, Limits};
+use chess::notation;
+use chess::piece::{Color, Piece, Role};
+use chess::variant;
+use std::io::{self, BufRead, Write};
+
+const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+const RESET: &str = "\x1b[0m";
+
+fn render(board: &Board, flipped: bool, color: bool) -> String {
+    let mut out = String::new();
+    let files: Vec<usize> = if flipped {
+        (0..board.width).rev().collect()
+    } else {
+        (0..board.width).collect()
+    };
+    let ranks: Vec<usize> = if flipped {
+        (0..board.height).rev().collect()
+    } else {
+        (0..board.height).collect()
+    };
+    for &y in &ranks {
+        out.push_str(&format!("{:>2} ", board.height - y));
+        for &x in &files {
+            let piece = board.piece_at(x, y);
+            if color {
+                // both sides use the solid glyphs and are told apart by colour
+                let background = if (x + y) % 2 == 0 { LIGHT_SQUARE } else { DARK_SQUARE };
+                let (foreground, glyph) = match piece {
+                    Some(piece) if piece.color == Color::White => {
+                        (WHITE_PIECE, Piece::new(Color::Black, piece.role).glyph().to_ascii_uppercase())
+                    }
+                    Some(piece) => (BLACK_PIECE, piece.glyph().to_ascii_uppercase()),
+                    None => (BLACK_PIECE, ' '),
+                };
+                out.push_str(&format!("{}{} {} {}", background, foreground, glyph, RESET));
+            } else if let Some(piece) = piece {
+                out.push_str(&format!(" {} ", piece.glyph()));
+            } else {
+                out.push_str(if (x + y) % 2 == 0 { " . " } else { " : " });
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("   ");
+    for &x in &files {
+        out.push_str(&format!(" {} ", (b'a' + x as u8) as char));
+    }
+    out.push('\n');
+    out
+}
+
+fn print_help() {
+    println!("enter moves as SAN (Nf3, exd5, O-O, e8=Q) or coordinates (g1f3, e7e8q)");
+    println!("other commands: go (the engine plays a move), flip, help, quit");
+    println!("new game: 960 [number] (a Chess960 start, random without a number), fen <FEN>; fen shows the position");
+    println!("variant <name> starts a new game of another variant, variant alone lists them");
+    println!("pgn shows the game so far; in Crazyhouse drop with N@f3 or @e4 for a pawn");
+}
+
+fn main() {
+    let color = std::env::var_os("NO_COLOR").is_none();
+    let mut variant = variant::ALL[0];
+    let mut board = Board::start_position(variant);
+    let mut turn = Color::White;
+    let mut fullmove = 1;
+    let mut flipped = false;
+    // the game so far, for pgn
+    let mut start = board.clone();
+    let mut played: Vec<Move> = Vec::new();
+    let mut engine = Engine::new();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    print_help();
+    loop {
+        println!();
+        print!("{}", render(&board, flipped, color));
+
+        let side = turn.name();
+        if let Some(end) = board.game_end() {
+            match end.winner {
+                Some(winner) => println!("{} wins ({})", winner.name(), end.reason),
+                None => println!("The game is a draw ({})", end.reason),
+            }
+            break;
+        }
+        if board.is_king_in_check(turn) {
+            println!("{} is in check", side);
+        }
+        if variant.counts_checks() {
+            let checks = board.checks();
+            println!("checks given: White {}, Black {}", checks[0], checks[1]);
+        }
+        if variant.drops() {
+            for color in [Color::White, Color::Black] {
+                let pocket = board.pocket(color);
+                let held: Vec<String> = Role::ALL
+                    .iter()
+                    .filter(|&&role| pocket[role as usize] > 0)
+                    .map(|&role| format!("{}{}", Piece::new(color, role).glyph(), pocket[role as usize]))
+                    .collect();
+                println!("{} in hand: {}", color.name(), if held.is_empty() { "-".to_string() } else { held.join(" ") });
+            }
+        }
+        print!("{} to move> ", side);
+        let _ = io::stdout().flush();
+
+        let Some(Ok(line)) = lines.next() else {
+            break;
+        };
+        match line.trim() {
+            "" => {}
+            "quit" | "exit" => break,
+            "help" => print_help(),
+            "flip" => flipped = !flipped,
+            "fen" => println!("{}", notation::to_fen(&board, fullmove)),
+            "pgn" => println!("{}", notation::to_pgn(&start, &played, "*", &[], &[])),
+            text if text.starts_with("fen ") => match notation::parse_fen(&text[4..], variant) {
+                Ok(position) => {
+                    board = position;
+                    turn = board.side_to_move();
+                    fullmove = text.split_whitespace().nth(6).and_then(|n| n.parse().ok()).unwrap_or(1);
+                    start = board.clone();
+                    played.clear();
+                }
+                Err(error) => println!("{}", error),
+            },
+            text if text == "960" || text.starts_with("960 ") => {
+                let index = match text[3..].trim() {
+                    "" => Some(board::random_chess960_index()),
+                    number => number.parse().ok().filter(|&index| index < 960),
+                };
+                let (height, back_rank) = variant.start();
+                match index {
+                    _ if height != 8 || back_rank.len() != 8 => println!("{} isn't played on an 8x8 board", variant.name()),
+                    Some(index) => {
+                        println!("Chess960 position {}", index);
+                        board = Board::chess960(index);
+                        board.set_variant(variant);
+                        turn = Color::White;
+                        fullmove = 1;
+                        start = board.clone();
+                        played.clear();
+                    }
+                    None => println!("positions are numbered 0 to 959"),
+                }
+            }
+            "variant" => {
+                let names: Vec<&str> = variant::ALL.iter().map(|variant| variant.name()).collect();
+                println!("playing {}; variants: {}", variant.name(), names.join(", "));
+            }
+            text if text.starts_with("variant ") => match variant::from_name(text[8..].trim()) {
+                Some(chosen) => {
+                    variant = chosen;
+                    board = Board::start_position(variant);
+                    turn = Color::White;
+                    fullmove = 1;
+                    start = board.clone();
+                    played.clear();
+                }
+                None => println!("no variant called {}", text[8..].trim()),
+            },
+            "go" => {
+                // the search has the board itself, the lines are written out against a copy
+                let searched = board.clone();
+                engine.set_game_history(engine::game_history(&start, &played));
+                let info = engine.search(&mut board, Limits::default(), |info| {
+                    let pv: Vec<String> = info.pv.iter().map(|&mv| notation::to_coordinates(&searched, mv)).collect();
+                    println!(
+                        "depth {} score {} nodes {} nps {} pv {}",
+                        info.depth,
+                        engine::format_score(info.score),
+                        info.nodes,
+                        info.nps,
+                        pv.join(" ")
+                    );
+                });
+                if let Some(mv) = info.best_move() {
+                    println!("{} plays {}", side, notation::to_coordinates(&board, mv));
+                    board.make_move(mv);
+                    played.push(mv);
+                    if turn == Color::Black {
+                        fullmove += 1;
+                    }
+                    turn = turn.opposite();
+                }
+            }
+            text => match notation::parse_move(&mut board, turn, text) {
+                Ok(mv) => {
+                    board.make_move(mv);
+                    played.push(mv);
+                    if turn == Color::Black {
+                        fullmove += 1;
+                    }
+                    turn = turn.opposite();
+                }
+                Err(error) => println!("{}", error),
+            },
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::piece::Color;
+
+// the largest board, 10x8 for Capablanca chess; smaller boards use its top left corner
+pub const MAX_WIDTH: usize = 10;
+pub const MAX_HEIGHT: usize = 8;
+pub const SQUARES: usize = MAX_WIDTH * MAX_HEIGHT;
+
+// squares are numbered y * MAX_WIDTH + x whatever the board's size, so the top left
+// square is 0 (y grows towards white)
+pub type Bitboard = u128;
+pub type Square = usize;
+
+pub fn square(x: usize, y: usize) -> Square {
+    y * MAX_WIDTH + x
+}
+
+pub fn square_x(square: Square) -> usize {
+    square % MAX_WIDTH
+}
+
+pub fn square_y(square: Square) -> usize {
+    square / MAX_WIDTH
+}
+
+pub fn bit(square: Square) -> Bitboard {
+    1 << square
+}
+
+// iterates the squares of the set bits, lowest first
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as Square;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+pub fn squares(bitboard: Bitboard) -> Squares {
+    Squares(bitboard)
+}
+
+// the squares of a `width` by `height` board, attacks are cut down to these
+pub fn area(width: usize, height: usize) -> Bitboard {
+    let rank = (1 << width) - 1;
+    (0..height).fold(0, |area, y| area | rank << square(0, y))
+}
+
+const fn on_board(x: i32, y: i32) -> bool {
+    x >= 0 && x < MAX_WIDTH as i32 && y >= 0 && y < MAX_HEIGHT as i32
+}
+
+const fn leaper_table(diffs: &[(i32, i32)]) -> [Bitboard; SQUARES] {
+    let mut table = [0; SQUARES];
+    let mut sq = 0;
+    while sq < SQUARES {
+        let x = (sq % MAX_WIDTH) as i32;
+        let y = (sq / MAX_WIDTH) as i32;
+        let mut i = 0;
+        while i < diffs.len() {
+            let (dx, dy) = diffs[i];
+            if on_board(x + dx, y + dy) {
+                table[sq] |= 1 << ((y + dy) * MAX_WIDTH as i32 + x + dx);
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+pub const KNIGHT_ATTACKS: [Bitboard; SQUARES] = leaper_table(&[
+    (2, 1),
+    (-2, 1),
+    (2, -1),
+    (-2, -1),
+    (1, 2),
+    (-1, 2),
+    (1, -2),
+    (-1, -2),
+]);
+
+pub const KING_ATTACKS: [Bitboard; SQUARES] = leaper_table(&[
+    (1, 1),
+    (-1, 1),
+    (1, -1),
+    (-1, -1),
+    (1, 0),
+    (-1, 0),
+    (0, -1),
+    (0, 1),
+]);
+
+pub const CAMEL_ATTACKS: [Bitboard; SQUARES] = leaper_table(&[
+    (3, 1),
+    (-3, 1),
+    (3, -1),
+    (-3, -1),
+    (1, 3),
+    (-1, 3),
+    (1, -3),
+    (-1, -3),
+]);
+
+const WAZIR_ATTACKS: [Bitboard; SQUARES] = leaper_table(&[(1, 0), (-1, 0), (0, 1), (0, -1)]);
+const FERZ_ATTACKS: [Bitboard; SQUARES] = leaper_table(&[(1, 1), (-1, 1), (1, -1), (-1, -1)]);
+
+// squares a pawn on `square` attacks, white pawns go towards y == 0
+const WHITE_PAWN_ATTACKS: [Bitboard; SQUARES] = leaper_table(&[(-1, -1), (1, -1)]);
+const BLACK_PAWN_ATTACKS: [Bitboard; SQUARES] = leaper_table(&[(-1, 1), (1, 1)]);
+
+pub fn pawn_attacks(color: Color, square: Square) -> Bitboard {
+    match color {
+        Color::White => WHITE_PAWN_ATTACKS[square],
+        Color::Black => BLACK_PAWN_ATTACKS[square],
+    }
+}
+
+// the first four directions step to higher square numbers, the last four to lower ones
+const DIRECTIONS: [(i32, i32); 8] = [(1, 0), (0, 1), (1, 1), (-1, 1), (-1, 0), (0, -1), (1, -1), (-1, -1)];
+const ROOK_DIRECTIONS: [usize; 4] = [0, 1, 4, 5];
+const BISHOP_DIRECTIONS: [usize; 4] = [2, 3, 6, 7];
+
+const fn ray_table() -> [[Bitboard; SQUARES]; 8] {
+    let mut table = [[0; SQUARES]; 8];
+    let mut dir = 0;
+    while dir < 8 {
+        let (dx, dy) = DIRECTIONS[dir];
+        let mut sq = 0;
+        while sq < SQUARES {
+            let mut x = (sq % MAX_WIDTH) as i32 + dx;
+            let mut y = (sq / MAX_WIDTH) as i32 + dy;
+            while on_board(x, y) {
+                table[dir][sq] |= 1 << (y * MAX_WIDTH as i32 + x);
+                x += dx;
+                y += dy;
+            }
+            sq += 1;
+        }
+        dir += 1;
+    }
+    table
+}
+
+const RAYS: [[Bitboard; SQUARES]; 8] = ray_table();
+
+// classical ray attacks: cut each ray off behind its first blocker
+fn ray_attacks(square: Square, occupied: Bitboard, dir: usize) -> Bitboard {
+    let ray = RAYS[dir][square];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let blocker = if dir < 4 {
+        blockers.trailing_zeros() as Square
+    } else {
+        Bitboard::BITS as Square - 1 - blockers.leading_zeros() as Square
+    };
+    ray ^ RAYS[dir][blocker]
+}
+
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    ROOK_DIRECTIONS
+        .iter()
+        .fold(0, |attacks, &dir| attacks | ray_attacks(square, occupied, dir))
+}
+
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    BISHOP_DIRECTIONS
+        .iter()
+        .fold(0, |attacks, &dir| attacks | ray_attacks(square, occupied, dir))
+}
+
+// every square a jump of `leap`, or any of its reflections, reaches from `square`
+pub fn leaper_attacks(leap: (i32, i32), square: Square) -> Bitboard {
+    match leap {
+        (1, 0) | (0, 1) => WAZIR_ATTACKS[square],
+        (1, 1) => FERZ_ATTACKS[square],
+        (2, 1) | (1, 2) => KNIGHT_ATTACKS[square],
+        (3, 1) | (1, 3) => CAMEL_ATTACKS[square],
+        (dx, dy) => reflections(dx, dy)
+            .map(|(dx, dy)| (square_x(square) as i32 + dx, square_y(square) as i32 + dy))
+            .filter(|&(x, y)| on_board(x, y))
+            .fold(0, |attacks, (x, y)| attacks | bit(self::square(x as usize, y as usize))),
+    }
+}
+
+// everything up to and including the first piece in `occupied` along `step`, or any of
+// its reflections, from `square`
+pub fn slider_attacks(step: (i32, i32), square: Square, occupied: Bitboard) -> Bitboard {
+    match step {
+        (1, 0) | (0, 1) => rook_attacks(square, occupied),
+        (1, 1) => bishop_attacks(square, occupied),
+        (dx, dy) => {
+            let mut attacks = 0;
+            for (dx, dy) in reflections(dx, dy) {
+                let (mut x, mut y) = (square_x(square) as i32 + dx, square_y(square) as i32 + dy);
+                while on_board(x, y) {
+                    let sq = self::square(x as usize, y as usize);
+                    attacks |= bit(sq);
+                    if occupied & bit(sq) != 0 {
+                        break;
+                    }
+                    (x, y) = (x + dx, y + dy);
+                }
+            }
+            attacks
+        }
+    }
+}
+
+// the up to eight directions a move pattern covers, mirrored and turned
+fn reflections(dx: i32, dy: i32) -> impl Iterator<Item = (i32, i32)> {
+    let mut all = Vec::with_capacity(8);
+    for (a, b) in [(dx, dy), (dy, dx)] {
+        for (sx, sy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+            if !all.contains(&(a * sx, b * sy)) {
+                all.push((a * sx, b * sy));
+            }
+        }
+    }
+    all.into_iter()
+}
@@ -0,0 +1,900 @@
+use crate::bitboard::{
+    self, Bitboard, KING_ATTACKS, SQUARES, Square, bit, leaper_attacks, pawn_attacks, slider_attacks, square, square_x,
+    square_y, squares,
+};
+use crate::piece::{Color, Piece, Role};
+use crate::variant::{self, GameEnd, Variant};
+use crate::zobrist::{self, Hash};
+use std::fmt;
+
+const MAX_POSSIBLE_LEGAL_MOVES: usize = 32; // technically 28 but rounding off to nearest two powers
+const MAX_POSITION_MOVES: usize = 256; // the record is 218, for the whole side to move
+
+// castling is the king moving onto its own rook, so it reads the same from any
+// starting files; promotions always name their piece; a drop puts a piece from the
+// pocket onto `to` and has `from` the same as `to`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<Role>,
+    pub drop: Option<Role>,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square, promotion: Option<Role>) -> Self {
+        Move { from, to, promotion, drop: None }
+    }
+
+    pub fn drop(role: Role, to: Square) -> Self {
+        Move { from: to, to, promotion: None, drop: Some(role) }
+    }
+}
+
+// coordinate notation, e.g. `e2e4` or `e7e8q`, and `N@f3` for a drop, with the ranks of
+// an eight rank board; `notation::to_coordinates` numbers them for any board
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(role) = self.drop {
+            let sq = self.to;
+            return write!(f, "{}@{}{}", role.letter(), (b'a' + square_x(sq) as u8) as char, 8 - square_y(sq));
+        }
+        for sq in [self.from, self.to] {
+            write!(f, "{}{}", (b'a' + square_x(sq) as u8) as char, 8 - square_y(sq))?;
+        }
+        if let Some(role) = self.promotion {
+            write!(f, "{}", role.letter().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+// what `make_move` hands back so `unmake_move` can restore the position
+#[derive(Clone, Copy)]
+pub struct Undo {
+    piece: Piece,
+    captured: Option<Piece>,
+    castled: bool,
+    castling_rooks: [Option<usize>; 4],
+    checks: [u8; 2],
+    pockets: [[u8; Role::COUNT]; 2],
+    promoted: Bitboard,
+    // what a capture blew up around its target, besides the capturer
+    exploded: [Option<(Square, Piece)>; 8],
+    en_passant: Option<Square>,
+    // not always the mover's colour, legal move queries try the other side's pieces too
+    side_to_move: Color,
+    hash: Hash,
+}
+
+#[derive(Clone)]
+pub struct Board{
+    pub width: usize,
+    pub height: usize,
+    // the squares inside `width` and `height`
+    area: Bitboard,
+    bitboards: [[Bitboard; Role::COUNT]; 2],
+    colors: [Bitboard; 2],
+    squares: [Option<Piece>; SQUARES],
+    side_to_move: Color,
+    // the square a pawn skipped over, only kept while an enemy pawn could take on it
+    en_passant: Option<Square>,
+    hash: Hash,
+    // the file of the rook each castling right belongs to, ordered like `castling_rights`;
+    // a king move clears both of its side's, a rook leaving or being taken clears its own
+    castling_rooks: [Option<usize>; 4],
+    variant: &'static dyn Variant,
+    // checks given by each side, only counted when the variant asks for it
+    checks: [u8; 2],
+    // pieces in hand for variants with drops, by colour and role, and the squares of
+    // promoted pieces, which go back into the pocket as pawns
+    pockets: [[u8; Role::COUNT]; 2],
+    promoted: Bitboard,
+}
+
+impl Board {
+    // no pieces and no castling rights, white to move
+    pub fn empty() -> Self {
+        Board::with_size(8, 8)
+    }
+
+    // an empty board of any size up to `bitboard::MAX_WIDTH` by `bitboard::MAX_HEIGHT`
+    pub fn with_size(width: usize, height: usize) -> Self {
+        Board {
+            width,
+            height,
+            area: bitboard::area(width, height),
+            bitboards: [[0; Role::COUNT]; 2],
+            colors: [0; 2],
+            squares: [None; SQUARES],
+            side_to_move: Color::White,
+            en_passant: None,
+            hash: 0,
+            castling_rooks: [None; 4],
+            variant: &variant::Standard,
+            checks: [0; 2],
+            pockets: [[0; Role::COUNT]; 2],
+            promoted: 0,
+        }
+    }
+
+    pub fn new() -> Self{
+        Board::from_back_rank(&variant::STANDARD_BACK_RANK, 8)
+    }
+
+    // the variant's own start position, which sets the board's size
+    pub fn start_position(variant: &'static dyn Variant) -> Self {
+        let (height, back_rank) = variant.start();
+        let mut board = Board::from_back_rank(back_rank, height);
+        board.set_variant(variant);
+        board
+    }
+
+    // the Chess960 start positions in the usual numbering, 518 is the standard one
+    pub fn chess960(index: u32) -> Self {
+        let mut back_rank = [None; 8];
+        let mut n = index as usize % 960;
+        back_rank[n % 4 * 2 + 1] = Some(Role::Bishop);
+        n /= 4;
+        back_rank[n % 4 * 2] = Some(Role::Bishop);
+        n /= 4;
+        let mut place = |role: Role, nth: usize| {
+            let x = (0..8).filter(|&x| back_rank[x].is_none()).nth(nth).expect("a free file");
+            back_rank[x] = Some(role);
+        };
+        place(Role::Queen, n % 6);
+        n /= 6;
+        // the second knight counts the free files after the first one has been placed
+        let knights = [(0, 0), (0, 1), (0, 2), (0, 3), (1, 1), (1, 2), (1, 3), (2, 2), (2, 3), (3, 3)];
+        let (first, second) = knights[n];
+        place(Role::Knight, first);
+        place(Role::Knight, second);
+        for role in [Role::Rook, Role::King, Role::Rook] {
+            place(role, 0);
+        }
+        Board::from_back_rank(&back_rank.map(|role| role.expect("every file is filled")), 8)
+    }
+
+    // as wide as `back_rank`, with pawns in front of it and black mirroring white
+    fn from_back_rank(back_rank: &[Role], height: usize) -> Self {
+        let mut board = Board::with_size(back_rank.len(), height);
+        for (x, &role) in back_rank.iter().enumerate() {
+            board.put(square(x, 0), Piece::new(Color::Black, role));
+            board.put(square(x, 1), Piece::new(Color::Black, Role::Pawn));
+            board.put(square(x, height - 2), Piece::new(Color::White, Role::Pawn));
+            board.put(square(x, height - 1), Piece::new(Color::White, role));
+        }
+        board.set_castling_rights([true; 4]);
+        board
+    }
+
+    // for setting up positions, `validate` says whether the result can be played
+    pub fn set_piece(&mut self, sq: Square, piece: Option<Piece>) {
+        self.remove(sq);
+        if let Some(piece) = piece {
+            self.put(sq, piece);
+        }
+    }
+
+    pub fn variant(&self) -> &'static dyn Variant {
+        self.variant
+    }
+
+    // switches the rules, dropping castling rights the variant doesn't have
+    pub fn set_variant(&mut self, variant: &'static dyn Variant) {
+        self.variant = variant;
+        if !variant.castling() {
+            self.set_castling_rooks([None; 4]);
+        }
+    }
+
+    pub fn checks(&self) -> [u8; 2] {
+        self.checks
+    }
+
+    pub fn set_checks(&mut self, color: Color, count: u8) {
+        let given = &mut self.checks[color as usize];
+        self.hash ^= zobrist::checks_key(color, *given) ^ zobrist::checks_key(color, count);
+        *given = count;
+    }
+
+    // indexed by `Role`
+    pub fn pocket(&self, color: Color) -> [u8; Role::COUNT] {
+        self.pockets[color as usize]
+    }
+
+    pub fn set_pocket_count(&mut self, color: Color, role: Role, count: u8) {
+        let held = &mut self.pockets[color as usize][role as usize];
+        self.hash ^= zobrist::pocket_key(color, role, *held) ^ zobrist::pocket_key(color, role, count);
+        *held = count;
+    }
+
+    pub fn is_promoted(&self, sq: Square) -> bool {
+        self.promoted & bit(sq) != 0
+    }
+
+    pub fn set_promoted(&mut self, sq: Square, promoted: bool) {
+        if promoted {
+            self.promoted |= bit(sq);
+        } else {
+            self.promoted &= !bit(sq);
+        }
+    }
+
+    // the game's result if it is over, with the side to move to play
+    pub fn game_end(&mut self) -> Option<GameEnd> {
+        if let Some(end) = self.variant.end(self) {
+            return Some(end);
+        }
+        let color = self.side_to_move;
+        (!self.has_legal_moves(color)).then(|| self.variant.end_without_moves(self))
+    }
+
+    pub fn set_side_to_move(&mut self, color: Color) {
+        self.hash ^= zobrist::side_key(self.side_to_move) ^ zobrist::side_key(color);
+        self.side_to_move = color;
+    }
+
+    // ordered like `castling_rights`, each right goes to the outermost rook on its side
+    // of the king, or to the corner when there is none so that `validate` complains
+    pub fn set_castling_rights(&mut self, rights: [bool; 4]) {
+        let mut rooks = [None; 4];
+        for (i, &right) in rights.iter().enumerate() {
+            if right {
+                let (color, king_side) = castling_side(i);
+                let corner = if king_side { self.width - 1 } else { 0 };
+                rooks[i] = Some(self.outermost_rook(color, king_side).unwrap_or(corner));
+            }
+        }
+        self.set_castling_rooks(rooks);
+    }
+
+    pub fn castling_rooks(&self) -> [Option<usize>; 4] {
+        self.castling_rooks
+    }
+
+    pub fn set_castling_rooks(&mut self, rooks: [Option<usize>; 4]) {
+        let old = self.castling_rights();
+        self.castling_rooks = rooks;
+        self.hash ^= zobrist::castling_key(old) ^ zobrist::castling_key(self.castling_rights());
+    }
+
+    fn back_rank(&self, color: Color) -> usize {
+        match color {
+            Color::White => self.height - 1,
+            Color::Black => 0,
+        }
+    }
+
+    // the file of the rook furthest from the king on one side of it, on the back rank
+    pub fn outermost_rook(&self, color: Color, king_side: bool) -> Option<usize> {
+        let y = self.back_rank(color);
+        let king_x = (0..self.width).find(|&x| self.squares[square(x, y)] == Some(Piece::new(color, Role::King)))?;
+        let is_rook = |&x: &usize| self.squares[square(x, y)] == Some(Piece::new(color, Role::Rook));
+        if king_side {
+            (king_x + 1..self.width).rev().find(is_rook)
+        } else {
+            (0..king_x).find(is_rook)
+        }
+    }
+
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    pub fn set_en_passant(&mut self, sq: Option<Square>) {
+        let file = |sq: Option<Square>| sq.map(square_x);
+        self.hash ^= zobrist::en_passant_key(file(self.en_passant)) ^ zobrist::en_passant_key(file(sq));
+        self.en_passant = sq;
+    }
+
+    fn square_name(&self, sq: Square) -> String {
+        format!("{}{}", (b'a' + square_x(sq) as u8) as char, self.height - square_y(sq))
+    }
+
+    // everything that stops a set up position from being played, in words
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        let mut kings_ok = true;
+        for color in [Color::White, Color::Black] {
+            match self.pieces(color, Role::King).count_ones() {
+                _ if !self.variant.royal_king() => {}
+                0 => problems.push(format!("{} has no king", color.name())),
+                1 => {}
+                kings => problems.push(format!("{} has {} kings", color.name(), kings)),
+            }
+            kings_ok &= self.pieces(color, Role::King).count_ones() == 1;
+            // a side starts with a pawn and a piece on every file, though dropping what was
+            // captured can put more than that on the board
+            if self.variant.drops() {
+                continue;
+            }
+            if self.pieces(color, Role::Pawn).count_ones() as usize > self.width {
+                problems.push(format!("{} has more than {} pawns", color.name(), self.width));
+            }
+            if self.pieces_of(color).count_ones() as usize > 2 * self.width {
+                problems.push(format!("{} has more than {} pieces", color.name(), 2 * self.width));
+            }
+        }
+        let back_ranks = self.pieces(Color::White, Role::Pawn) | self.pieces(Color::Black, Role::Pawn);
+        for sq in squares(back_ranks) {
+            if square_y(sq) == 0 || square_y(sq) == self.height - 1 {
+                problems.push(format!("the pawn on {} is on the first or last rank", self.square_name(sq)));
+            }
+        }
+        let waiting = self.side_to_move.opposite();
+        if kings_ok && self.is_king_in_check(waiting) {
+            problems.push(format!(
+                "{} is in check but it is {}'s move",
+                waiting.name(),
+                self.side_to_move.name()
+            ));
+        }
+
+        let names = ["White king side", "White queen side", "Black king side", "Black queen side"];
+        for (i, &rook) in self.castling_rooks.iter().enumerate() {
+            let Some(rook_x) = rook else {
+                continue;
+            };
+            let (color, king_side) = castling_side(i);
+            let y = self.back_rank(color);
+            let king = self.pieces(color, Role::King);
+            let king_x = square_x(king.trailing_zeros() as Square);
+            let king_placed = kings_ok && square_y(king.trailing_zeros() as Square) == y;
+            if !king_placed
+                || (rook_x > king_x) != king_side
+                || self.squares[square(rook_x, y)] != Some(Piece::new(color, Role::Rook))
+            {
+                problems.push(format!(
+                    "{} castling needs the king on its first rank and a rook on {} on that side of it",
+                    names[i],
+                    self.square_name(square(rook_x, y))
+                ));
+            }
+        }
+
+        // the pawn that just moved two squares stands in front of the en passant square,
+        // from the side to move's point of view, with the squares it crossed empty
+        if let Some(sq) = self.en_passant {
+            let (skipped_rank, forward) = match self.side_to_move {
+                Color::White => (2, 1),
+                Color::Black => (self.height - 3, -1isize),
+            };
+            let x = square_x(sq);
+            let pawn = square(x, (square_y(sq) as isize + forward) as usize);
+            let start = square(x, (square_y(sq) as isize - forward) as usize);
+            if square_y(sq) != skipped_rank
+                || self.squares[pawn] != Some(Piece::new(waiting, Role::Pawn))
+                || self.squares[sq].is_some()
+                || self.squares[start].is_some()
+            {
+                problems.push(format!(
+                    "en passant on {} needs a {} pawn that has just moved two squares past it",
+                    self.square_name(sq),
+                    waiting.name().to_lowercase()
+                ));
+            }
+        }
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    // the Zobrist key of the position, equal positions with the same rights share it
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    fn put(&mut self, sq: Square, piece: Piece) {
+        self.bitboards[piece.color as usize][piece.role as usize] |= bit(sq);
+        self.colors[piece.color as usize] |= bit(sq);
+        self.squares[sq] = Some(piece);
+        self.hash ^= zobrist::piece_key(piece, sq);
+    }
+
+    fn remove(&mut self, sq: Square) -> Option<Piece> {
+        let piece = self.squares[sq].take()?;
+        self.bitboards[piece.color as usize][piece.role as usize] &= !bit(sq);
+        self.colors[piece.color as usize] &= !bit(sq);
+        self.hash ^= zobrist::piece_key(piece, sq);
+        Some(piece)
+    }
+
+    pub fn pieces(&self, color: Color, role: Role) -> Bitboard {
+        self.bitboards[color as usize][role as usize]
+    }
+
+    pub fn pieces_of(&self, color: Color) -> Bitboard {
+        self.colors[color as usize]
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    // white king side, white queen side, black king side, black queen side
+    pub fn castling_rights(&self) -> [bool; 4] {
+        self.castling_rooks.map(|rook| rook.is_some())
+    }
+
+    pub fn piece_at(&self, x: usize, y: usize) -> Option<Piece> {
+        self.squares[square(x, y)]
+    }
+
+    pub fn piece_on(&self, sq: Square) -> Option<Piece> {
+        self.squares[sq]
+    }
+
+    pub fn is_capture(&self, mv: Move) -> bool {
+        let Some(piece) = self.squares[mv.from] else {
+            return false;
+        };
+        let is_enemy = self.squares[mv.to].is_some_and(|target| target.color != piece.color);
+        is_enemy || (piece.role == Role::Pawn && self.en_passant == Some(mv.to))
+    }
+
+    pub fn is_castling(&self, mv: Move) -> bool {
+        match (self.squares[mv.from], self.squares[mv.to]) {
+            (Some(king), Some(rook)) => king.role == Role::King && rook == Piece::new(king.color, Role::Rook),
+            _ => false,
+        }
+    }
+
+    // where the king and rook of a castling move end up, the g and f files or the c and d files
+    pub fn castling_squares(&self, mv: Move) -> (Square, Square) {
+        let y = square_y(mv.from);
+        if square_x(mv.to) > square_x(mv.from) {
+            (square(self.width - 2, y), square(self.width - 3, y))
+        } else {
+            (square(2, y), square(3, y))
+        }
+    }
+
+    // the legal castling move that puts the king on `from` onto `to`, so that moving the
+    // king two files (or clicking where it lands) castles like it always has
+    pub fn castling_to(&mut self, from: Square, to: Square) -> Option<Move> {
+        let color = self.squares[from]?.color;
+        let moves = self.legal_moves(color);
+        if moves.iter().any(|mv| mv.from == from && mv.to == to) {
+            return None;
+        }
+        moves
+            .into_iter()
+            .find(|&mv| mv.from == from && self.is_castling(mv) && self.castling_squares(mv).0 == to)
+    }
+
+    pub fn color_at(&self, x: usize, y: usize) -> Option<Color> {
+        self.piece_at(x, y).map(|piece| piece.color)
+    }
+
+    fn king_square(&self, color: Color) -> Square {
+        self.pieces(color, Role::King).trailing_zeros() as Square
+    }
+
+    pub fn king_position(&self, color: Color) -> (usize, usize) {
+        let sq = self.king_square(color);
+        (square_x(sq), square_y(sq))
+    }
+
+    // never true where the king isn't royal, or where captures explode and the kings touch
+    pub fn is_king_in_check(&self, color: Color) -> bool {
+        let king = self.pieces(color, Role::King);
+        if !self.variant.royal_king() || king == 0 {
+            return false;
+        }
+        let sq = self.king_square(color);
+        let kings_touch = KING_ATTACKS[sq] & self.pieces(color.opposite(), Role::King) != 0;
+        !(self.variant.explodes() && kings_touch) && self.is_square_attacked(sq, color.opposite())
+    }
+
+    // the squares a piece of `role` on `from` attacks, going by its movement descriptor;
+    // nothing for pawns, whose attacks depend on their colour
+    fn piece_attacks(&self, role: Role, from: Square, occupied: Bitboard) -> Bitboard {
+        let Some(movement) = role.movement() else {
+            return 0;
+        };
+        let leaps = movement.leaps.iter().fold(0, |attacks, &leap| attacks | leaper_attacks(leap, from));
+        let slides = movement
+            .slides
+            .iter()
+            .fold(0, |attacks, &step| attacks | slider_attacks(step, from, occupied));
+        (leaps | slides) & self.area
+    }
+
+    // looks outwards from `sq` with every piece's move pattern for a matching piece of `by`,
+    // which works because every pattern is the same backwards
+    pub fn is_square_attacked(&self, sq: Square, by: Color) -> bool {
+        let occupied = self.occupied();
+        pawn_attacks(by.opposite(), sq) & self.pieces(by, Role::Pawn) != 0
+            || Role::ALL.into_iter().any(|role| {
+                let pieces = self.pieces(by, role);
+                let can_take = role != Role::King || !self.variant.explodes();
+                pieces != 0 && can_take && self.piece_attacks(role, sq, occupied) & pieces != 0
+            })
+    }
+
+    // every piece of either colour attacking `sq`, whatever stands on it
+    pub fn attackers_of(&self, sq: Square) -> Bitboard {
+        self.attackers_through(sq, self.occupied())
+    }
+
+    // the attackers of `sq` among the pieces still in `occupied`, with sliders seeing through
+    // the squares left out of it, for playing out exchanges without moving anything
+    pub fn attackers_through(&self, sq: Square, occupied: Bitboard) -> Bitboard {
+        let both = |role: Role| self.pieces(Color::White, role) | self.pieces(Color::Black, role);
+        let pawns = pawn_attacks(Color::White, sq) & self.pieces(Color::Black, Role::Pawn)
+            | pawn_attacks(Color::Black, sq) & self.pieces(Color::White, Role::Pawn);
+        let attackers = Role::ALL
+            .into_iter()
+            .fold(pawns, |attackers, role| attackers | self.piece_attacks(role, sq, occupied) & both(role));
+        attackers & occupied
+    }
+
+    pub fn is_legal_move(&mut self, src_x: usize, src_y: usize, dist_x: usize, dist_y: usize) -> bool {
+        let Some(color) = self.color_at(src_x, src_y) else {
+            return true;
+        };
+        if self.color_at(dist_x, dist_y) == Some(color) {
+            return false;
+        }
+        let mv = Move::new(square(src_x, src_y), square(dist_x, dist_y), None);
+        self.leaves_king_safe(mv, color)
+    }
+
+    pub(crate) fn leaves_king_safe(&mut self, mv: Move, color: Color) -> bool {
+        let undo = self.make_move(mv);
+        let safe = !self.is_king_in_check(color);
+        self.unmake_move(mv, undo);
+        safe
+    }
+
+    // the pawn taken en passant stands beside the capturing pawn, not on its target
+    fn en_passant_victim(&self, mv: Move, piece: Piece) -> Option<Square> {
+        (piece.role == Role::Pawn && self.en_passant == Some(mv.to)).then(|| square(square_x(mv.to), square_y(mv.from)))
+    }
+
+    pub fn make_move(&mut self, mv: Move) -> Undo {
+        let undo_hash = self.hash;
+        let rights = self.castling_rights();
+        let en_passant = self.en_passant;
+        let castled = self.is_castling(mv);
+        let castling_rooks = self.castling_rooks;
+        let (pockets, promoted) = (self.pockets, self.promoted);
+        let piece = match mv.drop {
+            Some(role) => Piece::new(self.side_to_move, role),
+            None => self.remove(mv.from).expect("make_move from an empty square"),
+        };
+        let captured = if mv.drop.is_some() {
+            let held = self.pockets[piece.color as usize][piece.role as usize];
+            self.set_pocket_count(piece.color, piece.role, held - 1);
+            self.put(mv.to, piece);
+            None
+        } else if castled {
+            // the king and rook swap over each other, so both leave before either lands
+            let rook = self.remove(mv.to).expect("castling with a rook");
+            let (king_to, rook_to) = self.castling_squares(mv);
+            self.put(king_to, piece);
+            self.put(rook_to, rook);
+            None
+        } else {
+            let victim = self.en_passant_victim(mv, piece).unwrap_or(mv.to);
+            let captured = self.remove(victim);
+            let role = mv.promotion.unwrap_or(piece.role);
+            self.put(mv.to, Piece::new(piece.color, role));
+            if self.variant.drops() {
+                // what is taken goes into the taker's pocket, a promoted piece as a pawn
+                if let Some(captured) = captured {
+                    let role = if self.is_promoted(victim) { Role::Pawn } else { captured.role };
+                    let held = self.pockets[piece.color as usize][role as usize];
+                    self.set_pocket_count(piece.color, role, held + 1);
+                }
+                let moved_promoted = self.is_promoted(mv.from);
+                self.set_promoted(victim, false);
+                self.set_promoted(mv.from, false);
+                self.set_promoted(mv.to, moved_promoted || mv.promotion.is_some());
+            }
+            captured
+        };
+        let mut exploded = [None; 8];
+        if self.variant.explodes() && captured.is_some() {
+            self.remove(mv.to);
+            for (slot, sq) in exploded.iter_mut().zip(squares(KING_ATTACKS[mv.to])) {
+                if self.squares[sq].is_some_and(|piece| piece.role != Role::Pawn) {
+                    *slot = self.remove(sq).map(|piece| (sq, piece));
+                }
+            }
+        }
+        let undo = Undo {
+            piece,
+            captured,
+            castled,
+            castling_rooks,
+            checks: self.checks,
+            pockets,
+            promoted,
+            exploded,
+            en_passant,
+            side_to_move: self.side_to_move,
+            hash: undo_hash,
+        };
+
+        // a rook leaving its square or being captured there both lose that castling side, a
+        // king moving or blown up loses both
+        for (i, rook) in self.castling_rooks.iter_mut().enumerate() {
+            let (color, _) = castling_side(i);
+            let y = match color {
+                Color::White => self.height - 1,
+                Color::Black => 0,
+            };
+            let blown_up = |sq| exploded.iter().flatten().any(|&(exploded_sq, _)| exploded_sq == sq);
+            let king_blown_up = exploded.iter().flatten().any(|&(_, lost)| lost == Piece::new(color, Role::King));
+            let king_gone = piece.role == Role::King && piece.color == color || king_blown_up;
+            let lost = |x| mv.from == square(x, y) || mv.to == square(x, y) || blown_up(square(x, y));
+            if king_gone || rook.is_some_and(lost) {
+                *rook = None;
+            }
+        }
+
+        self.en_passant = None;
+        if piece.role == Role::Pawn && square_y(mv.from).abs_diff(square_y(mv.to)) == 2 {
+            let skipped = (mv.from + mv.to) / 2;
+            if pawn_attacks(piece.color, skipped) & self.pieces(piece.color.opposite(), Role::Pawn) != 0 {
+                self.en_passant = Some(skipped);
+            }
+        }
+        let file = |sq: Option<Square>| sq.map(square_x);
+        self.hash ^= zobrist::castling_key(rights) ^ zobrist::castling_key(self.castling_rights());
+        self.hash ^= zobrist::en_passant_key(file(en_passant)) ^ zobrist::en_passant_key(file(self.en_passant));
+        self.hash ^= zobrist::side_key(self.side_to_move) ^ zobrist::side_key(piece.color.opposite());
+        self.side_to_move = piece.color.opposite();
+        if self.variant.counts_checks() && self.is_king_in_check(self.side_to_move) {
+            let given = &mut self.checks[piece.color as usize];
+            self.hash ^= zobrist::checks_key(piece.color, *given) ^ zobrist::checks_key(piece.color, *given + 1);
+            *given += 1;
+        }
+        undo
+    }
+
+    pub fn unmake_move(&mut self, mv: Move, undo: Undo) {
+        self.en_passant = undo.en_passant;
+        if mv.drop.is_some() {
+            self.remove(mv.to);
+        } else if undo.castled {
+            let (king_to, rook_to) = self.castling_squares(mv);
+            self.remove(king_to);
+            let rook = self.remove(rook_to).expect("castled rook");
+            self.put(mv.to, rook);
+        } else {
+            self.remove(mv.to);
+            for &(sq, piece) in undo.exploded.iter().flatten() {
+                self.put(sq, piece);
+            }
+            if let Some(captured) = undo.captured {
+                let sq = self.en_passant_victim(mv, undo.piece).unwrap_or(mv.to);
+                self.put(sq, captured);
+            }
+        }
+        if mv.drop.is_none() {
+            self.put(mv.from, undo.piece);
+        }
+        self.castling_rooks = undo.castling_rooks;
+        self.checks = undo.checks;
+        self.pockets = undo.pockets;
+        self.promoted = undo.promoted;
+        self.side_to_move = undo.side_to_move;
+        self.hash = undo.hash;
+    }
+
+    fn push_targets(&self, moves: &mut Vec<Move>, from: Square, targets: Bitboard) {
+        for to in squares(targets) {
+            moves.push(Move::new(from, to, None));
+        }
+    }
+
+    fn push_pawn_move(&self, moves: &mut Vec<Move>, from: Square, to: Square, color: Color) {
+        let last_rank = match color {
+            Color::White => 0,
+            Color::Black => self.height - 1,
+        };
+        if square_y(to) == last_rank {
+            for &promotion in self.variant.promotions() {
+                moves.push(Move::new(from, to, Some(promotion)));
+            }
+        } else {
+            moves.push(Move::new(from, to, None));
+        }
+    }
+
+    // castling needs the king and rook unmoved, every square either of them crosses empty
+    // apart from the two of them, and the king's path (including where it starts) not attacked
+    fn push_castling_moves(&self, moves: &mut Vec<Move>, color: Color) {
+        let y = self.back_rank(color);
+        if !self.variant.castling() || self.pieces(color, Role::King) == 0 {
+            return;
+        }
+        let king = self.king_square(color);
+        if square_y(king) != y {
+            return;
+        }
+        let enemy = color.opposite();
+        for (i, rook_x) in self.castling_rooks.iter().enumerate() {
+            let (right_color, king_side) = castling_side(i);
+            let Some(rook_x) = *rook_x else {
+                continue;
+            };
+            let rook = square(rook_x, y);
+            if right_color != color
+                || (rook_x > square_x(king)) != king_side
+                || self.squares[rook] != Some(Piece::new(color, Role::Rook))
+            {
+                continue;
+            }
+            let mv = Move::new(king, rook, None);
+            let (king_to, rook_to) = self.castling_squares(mv);
+            let span = |a: Square, b: Square| square_x(a).min(square_x(b))..=square_x(a).max(square_x(b));
+            let others = self.occupied() & !bit(king) & !bit(rook);
+            let is_clear = span(king, king_to)
+                .chain(span(rook, rook_to))
+                .all(|x| others & bit(square(x, y)) == 0);
+            if is_clear && span(king, king_to).all(|x| !self.is_square_attacked(square(x, y), enemy)) {
+                moves.push(mv);
+            }
+        }
+    }
+
+    // moves that follow the piece patterns but may leave the own king in check
+    fn pseudo_legal_moves(&self, color: Color, moves: &mut Vec<Move>) {
+        let own = self.pieces_of(color);
+        let enemy = self.pieces_of(color.opposite());
+        let occupied = own | enemy;
+        let en_passant = match self.en_passant {
+            Some(sq) if color == self.side_to_move => bit(sq),
+            _ => 0,
+        };
+
+        for from in squares(own) {
+            let Some(piece) = self.squares[from] else {
+                continue;
+            };
+            if piece.role != Role::Pawn {
+                self.push_targets(moves, from, self.piece_attacks(piece.role, from, occupied) & !own);
+                continue;
+            }
+            let (start_rank, forward) = match color {
+                Color::White => (self.height - 2, -1),
+                Color::Black => (1, 1),
+            };
+            for to in squares(pawn_attacks(color, from) & (enemy | en_passant) & self.area) {
+                self.push_pawn_move(moves, from, to, color);
+            }
+            let step = |sq: Square| square(square_x(sq), (square_y(sq) as isize + forward) as usize);
+            let one_step = step(from);
+            if occupied & bit(one_step) == 0 {
+                self.push_pawn_move(moves, from, one_step, color);
+                // minichess boards are too short for the double step, and only a pawn on its
+                // start rank has a square two ahead to look at
+                if self.height >= 8 && square_y(from) == start_rank {
+                    let two_steps = step(one_step);
+                    if occupied & bit(two_steps) == 0 {
+                        moves.push(Move::new(from, two_steps, None));
+                    }
+                }
+            }
+        }
+        self.push_castling_moves(moves, color);
+        self.push_drops(moves, color);
+    }
+
+    // every piece in hand onto every empty square, pawns not on the first or last rank
+    fn push_drops(&self, moves: &mut Vec<Move>, color: Color) {
+        if !self.variant.drops() {
+            return;
+        }
+        let pocket = self.pockets[color as usize];
+        let empty = squares(!self.occupied() & self.area);
+        for to in empty {
+            for role in Role::ALL {
+                let back_rank = square_y(to) == 0 || square_y(to) == self.height - 1;
+                if pocket[role as usize] > 0 && !(role == Role::Pawn && back_rank) {
+                    moves.push(Move::drop(role, to));
+                }
+            }
+        }
+    }
+
+    pub fn legal_moves(&mut self, color: Color) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(MAX_POSITION_MOVES);
+        self.pseudo_legal_moves(color, &mut moves);
+        self.variant.filter_moves(self, color, &mut moves);
+        moves
+    }
+
+    pub fn get_legal_moves_for_piece(
+        &mut self,
+        src_x: usize,
+        src_y: usize,
+    ) -> Vec<(usize, usize)> {
+        let mut legal_moves: Vec<(usize, usize)> = Vec::with_capacity(MAX_POSSIBLE_LEGAL_MOVES);
+        let Some(color) = self.color_at(src_x, src_y) else {
+            return legal_moves;
+        };
+        let from = square(src_x, src_y);
+        for mv in self.legal_moves(color) {
+            if mv.from != from {
+                continue;
+            }
+            // castling can be played onto the rook or onto where the king lands
+            let mut targets = vec![mv.to];
+            if self.is_castling(mv) && self.castling_squares(mv).0 != from {
+                targets.push(self.castling_squares(mv).0);
+            }
+            // the four promotions share a destination
+            for target in targets {
+                let target = (square_x(target), square_y(target));
+                if !legal_moves.contains(&target) {
+                    legal_moves.push(target);
+                }
+            }
+        }
+        legal_moves
+    }
+
+    pub fn has_legal_moves(&mut self, color: Color) -> bool {
+        !self.legal_moves(color).is_empty()
+    }
+
+    // `promotion` is only used by pawns reaching the last rank, which otherwise become queens
+    pub fn move_piece(&mut self, src_x: usize, src_y: usize, x: usize, y: usize, promotion: Option<Role>) -> (Move, Undo) {
+        if let Some(castle) = self.castling_to(square(src_x, src_y), square(x, y)) {
+            return (castle, self.make_move(castle));
+        }
+        let promotion = match self.piece_at(src_x, src_y) {
+            Some(Piece { color: Color::White, role: Role::Pawn }) if y == 0 => Some(promotion.unwrap_or(Role::Queen)),
+            Some(Piece { color: Color::Black, role: Role::Pawn }) if y == self.height - 1 => {
+                Some(promotion.unwrap_or(Role::Queen))
+            }
+            _ => None,
+        };
+        let mv = Move::new(square(src_x, src_y), square(x, y), promotion);
+        (mv, self.make_move(mv))
+    }
+
+    // counts the leaf positions `depth` plies ahead, the usual way to check move generation
+    pub fn perft(&mut self, color: Color, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.legal_moves(color);
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut nodes = 0;
+        for mv in moves {
+            let undo = self.make_move(mv);
+            nodes += self.perft(color.opposite(), depth - 1);
+            self.unmake_move(mv, undo);
+        }
+        nodes
+    }
+}
+
+// the colour and side of the castling right at `index` in `castling_rights` order
+pub fn castling_side(index: usize) -> (Color, bool) {
+    let color = if index < 2 { Color::White } else { Color::Black };
+    (color, index.is_multiple_of(2))
+}
+
+// a Chess960 position number picked at random, for starting a game without choosing one
+pub fn random_chess960_index() -> u32 {
+    use std::hash::{BuildHasher, Hasher};
+    (std::collections::hash_map::RandomState::new().build_hasher().finish() % 960) as u32
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
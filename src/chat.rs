@@ -0,0 +1,174 @@
+use crate::setup::wrap;
+use crate::{BLOCKSIZE, Game, PANEL_WIDTH, WINDOW_HEIGHT};
+use chess::net::Message;
+use chess::piece::Color as Side;
+use macroquad::prelude::*;
+use macroquad::ui::root_ui;
+use std::time::Instant;
+
+// the chat sits in a column of its own beside the side panel in network games
+pub const CHAT_WIDTH: f32 = 220.0;
+const LINE_HEIGHT: f32 = 18.0;
+// roughly how many characters fit across the column
+const WRAP: usize = 28;
+const MAX_LENGTH: usize = 200;
+const PRESETS: [&str; 4] = ["Good luck", "Have fun", "Well played", "Thanks"];
+
+struct ChatLine {
+    // how many moves had been played, which is where the line goes in the PGN
+    ply: usize,
+    time: String,
+    // None for something this end has to say, like a declined draw, which isn't exported
+    from: Option<&'static str>,
+    text: String,
+}
+
+pub struct Chat {
+    lines: Vec<ChatLine>,
+    input: String,
+    // keys go to the chat box rather than the move box
+    typing: bool,
+    // the other side's messages are dropped, not just hidden
+    muted: bool,
+    started: Instant,
+}
+
+impl Default for Chat {
+    fn default() -> Self {
+        Chat {
+            lines: Vec::new(),
+            input: String::new(),
+            typing: false,
+            muted: false,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Chat {
+    pub fn note(&mut self, text: impl Into<String>) {
+        self.lines.push(ChatLine { ply: 0, time: String::new(), from: None, text: text.into() });
+    }
+
+    // stamped with the time into the game, which means the same at both ends
+    fn say(&mut self, ply: usize, from: &'static str, text: String) {
+        let seconds = self.started.elapsed().as_secs();
+        let time = format!("{:02}:{:02}", seconds / 60, seconds % 60);
+        self.lines.push(ChatLine { ply, time, from: Some(from), text });
+    }
+
+    pub fn hear(&mut self, ply: usize, from: &'static str, text: String) {
+        if !self.muted {
+            self.say(ply, from, text);
+        }
+    }
+
+    // what was said, as PGN comments paired with the move they followed
+    pub fn comments(&self) -> Vec<(usize, String)> {
+        self.lines
+            .iter()
+            .filter_map(|line| Some((line.ply, format!("[{}] {}: {}", line.time, line.from?, line.text))))
+            .collect()
+    }
+}
+
+impl Game {
+    fn send_chat(&mut self, text: &str) {
+        let text = text.trim();
+        let ply = self.moves.len();
+        let Some(online) = self.online.as_mut().filter(|_| !text.is_empty()) else {
+            return;
+        };
+        online.send(Message::Chat { text: text.to_string(), from: None });
+        let from = online.color.map_or("Spectator", Side::name);
+        online.chat.say(ply, from, text.to_string());
+    }
+
+    // while the chat box has the keys, Enter sends and Escape goes back to the board
+    pub fn handle_chat_keyboard(&mut self) -> bool {
+        let Some(online) = self.online.as_mut().filter(|online| online.chat.typing) else {
+            return false;
+        };
+        let chat = &mut online.chat;
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() && chat.input.len() < MAX_LENGTH {
+                chat.input.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            chat.input.pop();
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            chat.typing = false;
+        }
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+            let text = std::mem::take(&mut chat.input);
+            self.send_chat(&text);
+        }
+        true
+    }
+
+    pub fn chat_comments(&self) -> Vec<(usize, String)> {
+        self.online.as_ref().map(|online| online.chat.comments()).unwrap_or_default()
+    }
+
+    pub fn draw_chat_panel(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        let chat = &mut online.chat;
+        let x = self.board.width as f32 * BLOCKSIZE + PANEL_WIDTH + 10.0;
+        let width = CHAT_WIDTH - 20.0;
+        draw_text("Chat", x, 30.0, 24.0, WHITE);
+        if root_ui().button(vec2(x + 130.0, 12.0), if chat.muted { "Unmute" } else { "Mute" }) {
+            chat.muted = !chat.muted;
+        }
+
+        // newest at the bottom, as many as fit above the quick messages
+        let rows: Vec<(String, Color)> = chat
+            .lines
+            .iter()
+            .flat_map(|line| {
+                let (text, color) = match line.from {
+                    Some(from) => (format!("{} {}: {}", line.time, from, line.text), WHITE),
+                    None => (line.text.clone(), GRAY),
+                };
+                wrap(&text, WRAP).into_iter().map(move |row| (row, color))
+            })
+            .collect();
+        let (top, bottom) = (60.0, WINDOW_HEIGHT - 150.0);
+        let fit = ((bottom - top) / LINE_HEIGHT) as usize;
+        for (i, (row, color)) in rows[rows.len().saturating_sub(fit)..].iter().enumerate() {
+            draw_text(row, x, top + i as f32 * LINE_HEIGHT, 16.0, *color);
+        }
+
+        let mut said = None;
+        for (i, preset) in PRESETS.iter().enumerate() {
+            let position = vec2(x + (i % 2) as f32 * 100.0, WINDOW_HEIGHT - 130.0 + (i / 2) as f32 * 25.0);
+            if root_ui().button(position, *preset) {
+                said = Some(preset.to_string());
+            }
+        }
+
+        // a click on the box gives it the keys, a click anywhere else takes them back
+        let (box_y, box_height) = (WINDOW_HEIGHT - 70.0, 30.0);
+        if is_mouse_button_pressed(MouseButton::Left) {
+            let (mouse_x, mouse_y) = mouse_position();
+            chat.typing = (x..x + width).contains(&mouse_x) && (box_y..box_y + box_height).contains(&mouse_y);
+        }
+        draw_rectangle_lines(x, box_y, width, box_height, 2.0, if chat.typing { WHITE } else { GRAY });
+        // the end of a long message stays in view
+        let shown: String = chat.input.chars().rev().take(WRAP - 2).collect::<Vec<_>>().into_iter().rev().collect();
+        if shown.is_empty() && !chat.typing {
+            draw_text("say something", x + 6.0, box_y + 21.0, 18.0, DARKGRAY);
+        } else {
+            draw_text(&shown, x + 6.0, box_y + 21.0, 18.0, WHITE);
+        }
+        if root_ui().button(vec2(x, WINDOW_HEIGHT - 32.0), "Send") {
+            said = Some(std::mem::take(&mut chat.input));
+        }
+        if let Some(text) = said {
+            self.send_chat(&text);
+        }
+    }
+}
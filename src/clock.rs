@@ -0,0 +1,101 @@
+use crate::piece::Color;
+use serde::{Deserialize, Serialize};
+
+// minutes per side plus seconds added after every move, 0 minutes means no clock
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub minutes: u32,
+    pub increment: u32,
+}
+
+impl TimeControl {
+    const PRESETS: [TimeControl; 7] = [
+        TimeControl { minutes: 0, increment: 0 },
+        TimeControl { minutes: 1, increment: 0 },
+        TimeControl { minutes: 3, increment: 2 },
+        TimeControl { minutes: 5, increment: 0 },
+        TimeControl { minutes: 10, increment: 0 },
+        TimeControl { minutes: 15, increment: 10 },
+        TimeControl { minutes: 30, increment: 0 },
+    ];
+
+    pub fn name(self) -> String {
+        if self.minutes == 0 {
+            "Untimed".to_string()
+        } else {
+            format!("{}+{}", self.minutes, self.increment)
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::PRESETS
+            .iter()
+            .position(|&preset| preset == self)
+            .map_or(0, |index| index + 1);
+        Self::PRESETS[index % Self::PRESETS.len()]
+    }
+}
+
+pub struct Clock {
+    white: f32,
+    black: f32,
+    increment: f32,
+}
+
+impl Clock {
+    pub fn new(time_control: TimeControl) -> Option<Self> {
+        if time_control.minutes == 0 {
+            return None;
+        }
+        let seconds = time_control.minutes as f32 * 60.0;
+        Some(Clock {
+            white: seconds,
+            black: seconds,
+            increment: time_control.increment as f32,
+        })
+    }
+
+    pub fn remaining(&self, color: Color) -> f32 {
+        match color {
+            Color::White => self.white,
+            Color::Black => self.black,
+        }
+    }
+
+    // the other end of a network game has the final word on its own time
+    pub fn set(&mut self, color: Color, seconds: f32) {
+        match color {
+            Color::White => self.white = seconds,
+            Color::Black => self.black = seconds,
+        }
+    }
+
+    pub fn tick(&mut self, color: Color, seconds: f32) {
+        match color {
+            Color::White => self.white = (self.white - seconds).max(0.0),
+            Color::Black => self.black = (self.black - seconds).max(0.0),
+        }
+    }
+
+    // called once `color` has finished their move
+    pub fn press(&mut self, color: Color) {
+        match color {
+            Color::White => self.white += self.increment,
+            Color::Black => self.black += self.increment,
+        }
+    }
+
+    pub fn is_flagged(&self, color: Color) -> bool {
+        self.remaining(color) <= 0.0
+    }
+
+    pub fn format(&self, color: Color) -> String {
+        let remaining = self.remaining(color);
+        if remaining < 10.0 {
+            format!("{:.1}", remaining)
+        } else {
+            let seconds = remaining.ceil() as u32;
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        }
+    }
+}
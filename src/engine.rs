@@ -0,0 +1,814 @@
+use crate::bitboard::{SQUARES, Square, bit, square_x, square_y, squares};
+use crate::board::{Board, Move};
+use crate::piece::{Color, Role};
+use crate::variant::GameEnd;
+use crate::zobrist::Hash;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+pub const MATE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+const MAX_PLY: usize = 64;
+const TABLE_ENTRIES: usize = 1 << 19;
+// how often the clock is looked at, in nodes
+const TIME_CHECK_INTERVAL: u64 = 2048;
+
+// indexed by `Role`
+const VALUES: [i32; Role::COUNT] = [0, 900, 330, 320, 500, 100, 850, 880, 1200, 250];
+
+// piece-square tables from white's side, a8 first like the squares themselves;
+// black looks them up with the rank mirrored
+#[rustfmt::skip]
+const PIECE_SQUARE: [[i32; 64]; 6] = [
+    // king, kept for the middlegame, see KING_ENDGAME
+    [
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -30, -40, -40, -50, -50, -40, -40, -30,
+        -20, -30, -30, -40, -40, -30, -30, -20,
+        -10, -20, -20, -20, -20, -20, -20, -10,
+         20,  20,   0,   0,   0,   0,  20,  20,
+         20,  30,  10,   0,   0,  10,  30,  20,
+    ],
+    // queen
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+         -5,   0,   5,   5,   5,   5,   0,  -5,
+          0,   0,   5,   5,   5,   5,   0,  -5,
+        -10,   5,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,   0,   0,   0,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // bishop
+    [
+        -20, -10, -10, -10, -10, -10, -10, -20,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -10,   0,  10,  10,  10,  10,   0, -10,
+        -10,  10,  10,  10,  10,  10,  10, -10,
+        -10,   5,   0,   0,   0,   0,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // knight
+    [
+        -50, -40, -30, -30, -30, -30, -40, -50,
+        -40, -20,   0,   0,   0,   0, -20, -40,
+        -30,   0,  10,  15,  15,  10,   0, -30,
+        -30,   5,  15,  20,  20,  15,   5, -30,
+        -30,   0,  15,  20,  20,  15,   0, -30,
+        -30,   5,  10,  15,  15,  10,   5, -30,
+        -40, -20,   0,   5,   5,   0, -20, -40,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+    // rook
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+          5,  10,  10,  10,  10,  10,  10,   5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+         -5,   0,   0,   0,   0,   0,   0,  -5,
+          0,   0,   0,   5,   5,   0,   0,   0,
+    ],
+    // pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         50,  50,  50,  50,  50,  50,  50,  50,
+         10,  10,  20,  30,  30,  20,  10,  10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          0,   0,   0,  20,  20,   0,   0,   0,
+          5,  -5, -10,   0,   0, -10,  -5,   5,
+          5,  10,  10, -20, -20,  10,  10,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+];
+
+// once the pieces come off the king should walk to the centre
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+// the non-pawn material of the starting position, used to blend the king tables
+const OPENING_PHASE: i32 = 2 * (2 * 320 + 2 * 330 + 2 * 500 + 900);
+
+// the index into the 8x8 tables for `sq` on a board of any size, seen from `color`'s
+// side; the first and last files and ranks always land on the tables' edges
+fn table_square(board: &Board, color: Color, sq: Square) -> usize {
+    let y = match color {
+        Color::White => square_y(sq),
+        Color::Black => board.height - 1 - square_y(sq),
+    };
+    let scale = |v: usize, size: usize| (v * 7 + (size - 1) / 2) / (size - 1);
+    scale(y, board.height) * 8 + scale(square_x(sq), board.width)
+}
+
+// fairy pieces borrow the table of the piece they move most like
+fn table_role(role: Role) -> Role {
+    match role {
+        Role::Archbishop | Role::Camel => Role::Knight,
+        Role::Chancellor => Role::Rook,
+        Role::Amazon => Role::Queen,
+        role => role,
+    }
+}
+
+// material and piece placement in centipawns, from the side to move's point of view,
+// then pointed at whatever the variant's goal is
+pub fn evaluate(board: &Board) -> i32 {
+    board.variant().evaluate(board, material_and_placement(board))
+}
+
+fn material_and_placement(board: &Board) -> i32 {
+    let mut phase = 0;
+    for color in [Color::White, Color::Black] {
+        for role in Role::ALL.into_iter().filter(|&role| role != Role::King && role != Role::Pawn) {
+            phase += board.pieces(color, role).count_ones() as i32 * VALUES[role as usize];
+        }
+    }
+    let phase = phase.min(OPENING_PHASE);
+
+    let mut score = 0;
+    for color in [Color::White, Color::Black] {
+        let sign = if color == Color::White { 1 } else { -1 };
+        for role in Role::ALL {
+            for sq in squares(board.pieces(color, role)) {
+                let sq = table_square(board, color, sq);
+                let placement = match role {
+                    Role::King => {
+                        let middlegame = PIECE_SQUARE[Role::King as usize][sq];
+                        (middlegame * phase + KING_ENDGAME[sq] * (OPENING_PHASE - phase)) / OPENING_PHASE
+                    }
+                    _ => PIECE_SQUARE[table_role(role) as usize][sq],
+                };
+                score += sign * (VALUES[role as usize] + placement);
+            }
+        }
+    }
+    match board.side_to_move() {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+// a finished game scores like a mate at this ply, for the side to move
+fn end_score(end: GameEnd, color: Color, ply: usize) -> i32 {
+    match end.winner {
+        Some(winner) if winner == color => MATE - ply as i32,
+        Some(_) => -MATE + ply as i32,
+        None => 0,
+    }
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() >= MATE - MAX_PLY as i32
+}
+
+// `+0.35` in pawns, or `#3`/`#-3` for a mate in that many moves
+pub fn format_score(score: i32) -> String {
+    if is_mate_score(score) {
+        let moves = (MATE - score.abs() + 1) / 2;
+        if score > 0 {
+            format!("#{}", moves)
+        } else {
+            format!("#-{}", moves)
+        }
+    } else {
+        format!("{:+.2}", score as f32 / 100.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub depth: u32,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            depth: MAX_PLY as u32 - 1,
+            nodes: None,
+            time: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+// how well the computer plays; the weaker levels see less, misjudge positions
+// a little and now and then play a random move
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Level {
+    Beginner,
+    Novice,
+    Intermediate,
+    Club,
+    #[default]
+    Full,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Beginner => "Beginner",
+            Level::Novice => "Novice",
+            Level::Intermediate => "Intermediate",
+            Level::Club => "Club",
+            Level::Full => "Full strength",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Level::Beginner => Level::Novice,
+            Level::Novice => Level::Intermediate,
+            Level::Intermediate => Level::Club,
+            Level::Club => Level::Full,
+            Level::Full => Level::Beginner,
+        }
+    }
+
+    pub fn limits(self, time: Duration) -> Limits {
+        let (depth, nodes) = match self {
+            Level::Beginner => (1, Some(2_000)),
+            Level::Novice => (2, Some(10_000)),
+            Level::Intermediate => (3, Some(50_000)),
+            Level::Club => (5, Some(400_000)),
+            Level::Full => (MAX_PLY as u32 - 1, None),
+        };
+        Limits { depth, nodes, time: Some(time) }
+    }
+
+    // the most centipawns a position's evaluation may be off by
+    fn noise(self) -> i32 {
+        match self {
+            Level::Beginner => 150,
+            Level::Novice => 80,
+            Level::Intermediate => 30,
+            Level::Club | Level::Full => 0,
+        }
+    }
+
+    // the chance of ignoring the search and playing any legal move
+    fn blunder_chance(self) -> f64 {
+        match self {
+            Level::Beginner => 0.2,
+            Level::Novice => 0.08,
+            Level::Intermediate => 0.02,
+            Level::Club | Level::Full => 0.0,
+        }
+    }
+}
+
+// xorshift64, seeded from the system clock
+struct Random(u64);
+
+impl Random {
+    fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Random(nanos | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // uniform in 0..1
+    fn chance(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// what the last finished iteration found
+#[derive(Clone, Debug, Default)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub score: i32,
+    pub nodes: u64,
+    pub nps: u64,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<Move> {
+        self.pv.first().copied()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    // the score is at least this, the search failed high
+    Lower,
+    // the score is at most this, every move failed low
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    key: Hash,
+    best: Option<Move>,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+}
+
+// mate scores are stored relative to the node so they stay right when reached by another path
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -(MATE - MAX_PLY as i32) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+// a king can take back too, but only when nothing is left to take it
+fn exchange_value(role: Role) -> i32 {
+    if role == Role::King { 2 * MATE } else { VALUES[role as usize] }
+}
+
+// static exchange evaluation: what the capture `mv` wins once both sides have taken back on
+// its square with their least valuable piece for as long as it pays
+pub fn see(board: &Board, mv: Move) -> i32 {
+    let Some(attacker) = board.piece_on(mv.from).filter(|_| mv.drop.is_none()) else {
+        return 0;
+    };
+    // en passant has no piece on the target square but takes a pawn
+    let victim = board.piece_on(mv.to).map_or(Role::Pawn, |piece| piece.role);
+    let mut gains = [0; 32];
+    gains[0] = VALUES[victim as usize];
+    let mut on_square = attacker.role;
+    if let Some(promotion) = mv.promotion {
+        gains[0] += VALUES[promotion as usize] - VALUES[Role::Pawn as usize];
+        on_square = promotion;
+    }
+    let mut occupied = board.occupied() & !bit(mv.from);
+    let mut color = attacker.color.opposite();
+    let mut depth = 0;
+    while depth + 1 < gains.len() {
+        let attackers = board.attackers_through(mv.to, occupied) & board.pieces_of(color);
+        let Some((from, role)) = Role::ALL
+            .into_iter()
+            .filter_map(|role| squares(attackers & board.pieces(color, role)).next().map(|sq| (sq, role)))
+            .min_by_key(|&(_, role)| exchange_value(role))
+        else {
+            break;
+        };
+        depth += 1;
+        gains[depth] = exchange_value(on_square) - gains[depth - 1];
+        occupied &= !bit(from);
+        on_square = role;
+        color = color.opposite();
+    }
+    // each side may stop taking back when carrying on would lose more
+    while depth > 0 {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+        depth -= 1;
+    }
+    gains[0]
+}
+
+// exchanges only add up this way when captures take one piece and the king has to be kept
+fn counts_exchanges(board: &Board) -> bool {
+    board.variant().royal_king() && !board.variant().explodes()
+}
+
+// the positions before each of `moves` since the last pawn move or capture, the only ones
+// that can come round again; in Crazyhouse a capture can be dropped back, so all of them
+pub fn game_history(start: &Board, moves: &[Move]) -> Vec<Hash> {
+    let mut board = start.clone();
+    let mut hashes = Vec::with_capacity(moves.len());
+    for &mv in moves {
+        let pawn_move = mv.drop.is_none() && board.piece_on(mv.from).is_some_and(|piece| piece.role == Role::Pawn);
+        if (pawn_move || board.is_capture(mv)) && !board.variant().drops() {
+            hashes.clear();
+        } else {
+            hashes.push(board.hash());
+        }
+        board.make_move(mv);
+    }
+    hashes
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -(MATE - MAX_PLY as i32) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+// alpha-beta with iterative deepening; the transposition table and the
+// history heuristic carry over from one search to the next
+pub struct Engine {
+    table: Vec<Option<Entry>>,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    history: Box<[[i32; SQUARES]; SQUARES]>,
+    // pv[ply] is the best line found from that ply
+    pv: Vec<Vec<Move>>,
+    // positions on the current line and from the game before it, for repetitions
+    path: Vec<Hash>,
+    game: Vec<Hash>,
+    nodes: u64,
+    root_depth: u32,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    stopped: bool,
+    // set from another thread to end the search early
+    stop: Arc<AtomicBool>,
+    // root moves left out, to find the second, third... best lines
+    excluded: Vec<Move>,
+    // evaluation noise for the weaker levels, fixed per position and for the whole game so
+    // the table stays consistent
+    noise: i32,
+    noise_seed: u64,
+    random: Random,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let mut random = Random::new();
+        Engine {
+            table: vec![None; TABLE_ENTRIES],
+            killers: [[None; 2]; MAX_PLY],
+            history: Box::new([[0; SQUARES]; SQUARES]),
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            path: Vec::with_capacity(MAX_PLY),
+            game: Vec::new(),
+            nodes: 0,
+            root_depth: 0,
+            node_limit: None,
+            deadline: None,
+            stopped: false,
+            stop: Arc::new(AtomicBool::new(false)),
+            excluded: Vec::new(),
+            noise: 0,
+            noise_seed: random.next(),
+            random,
+        }
+    }
+
+    // setting the flag ends the running search as soon as possible; it is not
+    // cleared by the engine, so reset it before starting another search
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    // the positions the game went through to reach the one searched, from `game_history`;
+    // kept for every search until set again
+    pub fn set_game_history(&mut self, hashes: Vec<Hash>) {
+        self.game = hashes;
+    }
+
+    // forgets everything learnt, for a new game
+    pub fn clear(&mut self) {
+        self.table.fill(None);
+        *self.history = [[0; SQUARES]; SQUARES];
+        self.noise_seed = self.random.next();
+    }
+
+    // picks a move for the side to move the way `level` would, within `time`
+    pub fn play(&mut self, board: &mut Board, level: Level, time: Duration) -> SearchInfo {
+        self.set_noise(level.noise());
+        let mut info = self.iterate(board, level.limits(time), 1, |_| {}).swap_remove(0);
+        if self.random.chance() < level.blunder_chance() {
+            let moves = board.legal_moves(board.side_to_move());
+            if !moves.is_empty() {
+                let mv = moves[self.random.next() as usize % moves.len()];
+                info.pv = vec![mv];
+            }
+        }
+        info
+    }
+
+    // scores from a differently blurred evaluation don't mix
+    fn set_noise(&mut self, noise: i32) {
+        if noise != self.noise {
+            self.table.fill(None);
+            self.noise = noise;
+        }
+    }
+
+    // searches the side to move at full strength, calling `report` after every finished depth
+    pub fn search(&mut self, board: &mut Board, limits: Limits, mut report: impl FnMut(&SearchInfo)) -> SearchInfo {
+        self.set_noise(0);
+        self.iterate(board, limits, 1, |lines| report(&lines[0])).swap_remove(0)
+    }
+
+    // the best `count` moves with their lines, best first
+    pub fn search_lines(
+        &mut self,
+        board: &mut Board,
+        limits: Limits,
+        count: usize,
+        report: impl FnMut(&[SearchInfo]),
+    ) -> Vec<SearchInfo> {
+        self.set_noise(0);
+        self.iterate(board, limits, count, report)
+    }
+
+    // always returns at least one line, empty when there is nothing to play
+    fn iterate(
+        &mut self,
+        board: &mut Board,
+        limits: Limits,
+        count: usize,
+        mut report: impl FnMut(&[SearchInfo]),
+    ) -> Vec<SearchInfo> {
+        let start = Instant::now();
+        self.node_limit = limits.nodes;
+        self.deadline = limits.time.map(|time| start + time);
+        self.nodes = 0;
+        self.stopped = false;
+        self.killers = [[None; 2]; MAX_PLY];
+        for scores in self.history.iter_mut() {
+            for score in scores.iter_mut() {
+                *score /= 2;
+            }
+        }
+
+        let count = count.clamp(1, board.legal_moves(board.side_to_move()).len().max(1));
+        let mut lines = vec![SearchInfo::default()];
+        for depth in 1..=limits.depth.clamp(1, MAX_PLY as u32 - 1) {
+            self.root_depth = depth;
+            let mut found = Vec::with_capacity(count);
+            self.excluded.clear();
+            while found.len() < count {
+                let score = self.negamax(board, depth, 0, -INFINITY, INFINITY);
+                if self.stopped {
+                    break;
+                }
+                let elapsed = start.elapsed();
+                found.push(SearchInfo {
+                    depth,
+                    score,
+                    nodes: self.nodes,
+                    nps: (self.nodes as f64 / elapsed.as_secs_f64().max(0.001)) as u64,
+                    pv: self.pv[0].clone(),
+                });
+                match self.pv[0].first() {
+                    Some(&mv) => self.excluded.push(mv),
+                    None => break,
+                }
+            }
+            self.excluded.clear();
+            if self.stopped {
+                break;
+            }
+            lines = found;
+            report(&lines);
+            let elapsed = start.elapsed();
+            if lines[0].pv.is_empty() || (count == 1 && is_mate_score(lines[0].score)) {
+                break;
+            }
+            // the next depth takes several times as long, don't start what can't finish
+            if let Some(time) = limits.time
+                && elapsed * 2 > time
+            {
+                break;
+            }
+        }
+        lines
+    }
+
+    // the first depth always finishes so there is a move to play
+    fn should_stop(&mut self) -> bool {
+        if self.root_depth > 1
+            && (self.node_limit.is_some_and(|limit| self.nodes >= limit)
+                || self.nodes.is_multiple_of(TIME_CHECK_INTERVAL)
+                    && self.deadline.is_some_and(|deadline| Instant::now() >= deadline))
+        {
+            self.stopped = true;
+        }
+        if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) && self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        if self.noise == 0 {
+            return evaluate(board);
+        }
+        let offset = (board.hash() ^ self.noise_seed) % (2 * self.noise as u64 + 1);
+        evaluate(board) + offset as i32 - self.noise
+    }
+
+    fn probe(&self, key: Hash) -> Option<Entry> {
+        self.table[key as usize % TABLE_ENTRIES].filter(|entry| entry.key == key)
+    }
+
+    // always replaces, the newest search of a position is usually the most useful
+    fn store(&mut self, entry: Entry) {
+        self.table[entry.key as usize % TABLE_ENTRIES] = Some(entry);
+    }
+
+    fn negamax(&mut self, board: &mut Board, depth: u32, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv[ply].clear();
+        if self.should_stop() {
+            return 0;
+        }
+        let key = board.hash();
+        if ply > 0 && (self.path.contains(&key) || self.game.contains(&key)) {
+            return 0;
+        }
+        let color = board.side_to_move();
+        if let Some(end) = board.variant().end(board) {
+            return end_score(end, color, ply);
+        }
+        let in_check = board.is_king_in_check(color);
+        // look one move further when in check so mates at the horizon aren't missed
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(board);
+        }
+        self.nodes += 1;
+
+        // nodes searched with an open window are on the principal variation, they
+        // never stop at a table hit so the whole line gets filled in
+        let is_pv = beta - alpha > 1;
+        let mut hash_move = None;
+        if let Some(entry) = self.probe(key) {
+            hash_move = entry.best;
+            let score = score_from_table(entry.score, ply);
+            if !is_pv && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        let mut moves = board.legal_moves(color);
+        if moves.is_empty() {
+            return end_score(board.variant().end_without_moves(board), color, ply);
+        }
+        if ply == 0 {
+            moves.retain(|mv| !self.excluded.contains(mv));
+        }
+        self.order_moves(board, &mut moves, hash_move, ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        self.path.push(key);
+        for (index, mv) in moves.into_iter().enumerate() {
+            let is_quiet = !board.is_capture(mv) && mv.promotion.is_none();
+            let undo = board.make_move(mv);
+            // after the first move only prove the rest are worse, with a null window,
+            // and search again properly if one turns out better
+            let mut score = if index == 0 {
+                -self.negamax(board, depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                -self.negamax(board, depth - 1, ply + 1, -alpha - 1, -alpha)
+            };
+            if index > 0 && score > alpha && score < beta && !self.stopped {
+                score = -self.negamax(board, depth - 1, ply + 1, -beta, -alpha);
+            }
+            board.unmake_move(mv, undo);
+            if self.stopped {
+                self.path.pop();
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+                let (line, rest) = self.pv.split_at_mut(ply + 1);
+                line[ply].clear();
+                line[ply].push(mv);
+                line[ply].extend_from_slice(&rest[0]);
+            }
+            if alpha >= beta {
+                if is_quiet {
+                    if self.killers[ply][0] != Some(mv) {
+                        self.killers[ply] = [Some(mv), self.killers[ply][0]];
+                    }
+                    self.history[mv.from][mv.to] += (depth * depth) as i32;
+                }
+                break;
+            }
+        }
+        self.path.pop();
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // with root moves left out the score isn't the position's
+        if ply > 0 || self.excluded.is_empty() {
+            self.store(Entry {
+                key,
+                best: best_move,
+                depth,
+                score: score_to_table(best_score, ply),
+                bound,
+            });
+        }
+        best_score
+    }
+
+    // only captures and promotions, so the evaluation isn't taken in the middle of an exchange
+    fn quiescence(&mut self, board: &mut Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+        let color = board.side_to_move();
+        if let Some(end) = board.variant().end(board) {
+            return end_score(end, color, ply);
+        }
+        let stand_pat = self.evaluate(board);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        // captures that lose material once the square has been fought over aren't worth a look
+        let exchanges = counts_exchanges(board);
+        let mut moves = board.legal_moves(color);
+        moves.retain(|&mv| mv.promotion.is_some() || board.is_capture(mv) && !(exchanges && see(board, mv) < 0));
+        self.order_moves(board, &mut moves, None, ply);
+        for mv in moves {
+            let undo = board.make_move(mv);
+            let score = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move(mv, undo);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    // hash move first, then captures by most valuable victim and least valuable
+    // attacker, then the killers, captures that lose the exchange and finally quiet moves
+    // by history
+    fn order_moves(&self, board: &Board, moves: &mut [Move], hash_move: Option<Move>, ply: usize) {
+        let exchanges = counts_exchanges(board);
+        moves.sort_by_cached_key(|&mv| {
+            let losing = || exchanges && board.is_capture(mv) && see(board, mv) < 0;
+            let score = if Some(mv) == hash_move {
+                1_000_000
+            } else if board.is_capture(mv) && !losing() {
+                // en passant has no piece on the target square but takes a pawn
+                let victim = board.piece_on(mv.to).map_or(Role::Pawn, |piece| piece.role);
+                let attacker = board.piece_on(mv.from).map_or(Role::Pawn, |piece| piece.role);
+                100_000 + VALUES[victim as usize] * 10 - VALUES[attacker as usize] / 10
+            } else if mv.promotion == Some(Role::Queen) {
+                95_000
+            } else if self.killers[ply][0] == Some(mv) {
+                90_000
+            } else if self.killers[ply][1] == Some(mv) {
+                85_000
+            } else if board.is_capture(mv) {
+                81_000 + see(board, mv).max(-1_000)
+            } else {
+                self.history[mv.from][mv.to].min(80_000)
+            };
+            -score
+        });
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
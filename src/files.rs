@@ -0,0 +1,159 @@
+use crate::settings::Opponent;
+use crate::setup;
+use crate::{BLOCKSIZE, Game, GameCondition, PANEL_WIDTH, WINDOW_HEIGHT};
+use chess::board::Move;
+use chess::clock::{Clock, TimeControl};
+use chess::engine::Level;
+use chess::notation;
+use chess::piece::Color as Side;
+use chess::variant;
+use macroquad::prelude::request_new_screen_size;
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// a game in progress as it goes to disk, without what only matters for a frame or two
+// like a picked up piece, a half typed move or an animation
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    variant: String,
+    // the position the game started from and every move since, in coordinate notation
+    fen: String,
+    moves: Vec<String>,
+    turn: Side,
+    time_control: TimeControl,
+    // White's and Black's seconds left
+    clock: Option<[f32; 2]>,
+    opponent: Opponent,
+    level: Level,
+    hints: [u32; 2],
+    chess960: Option<u32>,
+}
+
+// exported games go somewhere easy to find, unlike the settings
+fn games_dir() -> Option<PathBuf> {
+    dirs::document_dir().or_else(dirs::home_dir).map(|dir| dir.join("chess"))
+}
+
+// one saved game at a time, quitting overwrites it
+fn saved_game_path() -> Option<PathBuf> {
+    games_dir().map(|dir| dir.join("saved-game.json"))
+}
+
+pub fn has_saved_game() -> bool {
+    saved_game_path().is_some_and(|path| path.exists())
+}
+
+impl Game {
+    fn result_text(&self) -> &'static str {
+        match self.game_condition {
+            GameCondition::WhiteWin => "1-0",
+            GameCondition::BlackWin => "0-1",
+            GameCondition::Draw => "1/2-1/2",
+            _ => "*",
+        }
+    }
+
+    // the game as a PGN file named after when it was saved, with any hints used as tags;
+    // a network game's chat goes in as comments
+    pub fn export_pgn(&self) -> io::Result<PathBuf> {
+        let dir = games_dir().ok_or(io::Error::new(ErrorKind::NotFound, "no folder to save games in"))?;
+        std::fs::create_dir_all(&dir)?;
+        let moves: Vec<Move> = self.moves.iter().map(|&(mv, _)| mv).collect();
+        let [white, black] = self.metadata.hints;
+        let tags = if white + black > 0 {
+            vec![("WhiteHints", white.to_string()), ("BlackHints", black.to_string())]
+        } else {
+            Vec::new()
+        };
+        let pgn = notation::to_pgn(&self.start, &moves, self.result_text(), &tags, &self.chat_comments());
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let path = dir.join(format!("game-{}.pgn", seconds));
+        std::fs::write(&path, pgn)?;
+        Ok(path)
+    }
+
+    // a game that was started and hasn't ended, network games belong to the other end too
+    fn is_unfinished(&self) -> bool {
+        let playing = matches!(self.game_condition, GameCondition::Running) || self.paused;
+        playing && self.online.is_none() && !self.moves.is_empty()
+    }
+
+    pub fn save_game(&self) -> io::Result<PathBuf> {
+        let path = saved_game_path().ok_or(io::Error::new(ErrorKind::NotFound, "no folder to save games in"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let saved = SavedGame {
+            variant: self.start.variant().name().to_string(),
+            fen: notation::to_fen(&self.start, 1),
+            moves: self.history(),
+            turn: self.turn,
+            time_control: self.settings.time_control,
+            clock: self.clock.as_ref().map(|clock| [clock.remaining(Side::White), clock.remaining(Side::Black)]),
+            opponent: self.settings.opponent,
+            level: self.settings.level,
+            hints: self.metadata.hints,
+            chess960: self.metadata.chess960,
+        };
+        let json = serde_json::to_string_pretty(&saved).map_err(io::Error::other)?;
+        std::fs::write(&path, json)?;
+        Ok(path)
+    }
+
+    pub fn load_game(&mut self) -> Result<(), String> {
+        let path = saved_game_path().ok_or("no folder to load games from")?;
+        let json = std::fs::read_to_string(&path).map_err(|error| error.to_string())?;
+        let saved: SavedGame = serde_json::from_str(&json).map_err(|error| error.to_string())?;
+        let variant = variant::from_name(&saved.variant).ok_or(format!("unknown variant {}", saved.variant))?;
+        let start = notation::parse_fen(&saved.fen, variant)?;
+        // nothing changes until the whole game has been played through on a board of its own
+        let mut board = start.clone();
+        let played = setup::replay(&mut board, &saved.moves)?;
+        if board.side_to_move() != saved.turn {
+            return Err("the saved moves don't match whose turn it was".to_string());
+        }
+
+        self.online = None;
+        self.settings.time_control = saved.time_control;
+        self.settings.opponent = saved.opponent;
+        self.settings.level = saved.level;
+        self.settings.variant = variant.name().to_string();
+        self.settings.chess960 = saved.chess960.is_some();
+        let _ = self.settings.save();
+
+        self.play_from(start, false);
+        self.board = board;
+        self.moves = played;
+        self.turn = saved.turn;
+        self.clock = Clock::new(saved.time_control);
+        if let (Some(clock), Some([white, black])) = (&mut self.clock, saved.clock) {
+            clock.set(Side::White, white);
+            clock.set(Side::Black, black);
+        }
+        self.metadata.hints = saved.hints;
+        self.metadata.chess960 = saved.chess960;
+        self.metadata.end_reason = None;
+        self.move_input.clear();
+        self.input_error = None;
+        self.cursor = None;
+        self.search_info = None;
+        self.paused = false;
+        request_new_screen_size(self.board.width as f32 * BLOCKSIZE + PANEL_WIDTH, WINDOW_HEIGHT);
+        Ok(())
+    }
+
+    // a paused game about to make way for something else is kept, the same as on quitting
+    pub fn save_unfinished(&mut self) {
+        if self.is_unfinished() {
+            let _ = self.save_game();
+        }
+    }
+
+    // closing the window keeps an unfinished game to carry on with next time
+    pub fn quit(&mut self) {
+        self.save_unfinished();
+        self.game_condition = GameCondition::Quit;
+    }
+}
@@ -0,0 +1,10 @@
+pub mod bitboard;
+pub mod board;
+pub mod clock;
+pub mod engine;
+pub mod net;
+pub mod notation;
+pub mod piece;
+pub mod review;
+pub mod variant;
+pub mod zobrist;
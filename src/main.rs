@@ -1,26 +1,43 @@
 use macroquad::prelude::*;
 use macroquad::ui::{hash, root_ui, widgets};
+
+mod analysis;
+mod chat;
+mod files;
+mod online;
+mod pocket;
+mod review_screen;
+mod rooms;
+mod settings;
+mod setup;
+mod theme;
+
+use chess::bitboard::{square, square_x, square_y, squares};
+use analysis::Analysis;
+use online::{Lobby, Online};
+use review_screen::ReviewScreen;
+use rooms::Rooms;
+use chess::board::{self, Board, Move, Undo};
+use chess::clock::Clock;
+use chess::engine::{self, Engine, Limits, SearchInfo};
+use chess::notation;
+// macroquad already has a `Color`
+use chess::piece::{Color as Side, Piece, Role};
+use chess::variant;
+use chess::zobrist::Hash;
+use settings::Settings;
+use setup::Setup;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use theme::{PieceSets, Theme};
+
 const BLOCKSIZE: f32 = 75.0;
-const SPRITESIZE: f32 = 45.0;
 const BUFF: f32 = 2.0;
-const MAX_POSSIBLE_LEGAL_MOVES: usize = 32; // technically 28 but rounding off to nearest two powers
-
-#[derive(Clone, Copy, PartialEq, Eq,Debug)]
-enum PieceType {
-    WK,
-    BK,
-    W,
-    B,
-    WQ,
-    BQ,
-    WR,
-    BR,
-    WN,
-    BN,
-    WB,
-    BB,
-    E,
-}
+// the side panel beside the board, the window is as wide as both together
+const PANEL_WIDTH: f32 = 200.0;
+const WINDOW_HEIGHT: f32 = 600.0;
 
 enum GameCondition{
     Running,
@@ -28,1074 +45,818 @@ enum GameCondition{
     WhiteWin,
     BlackWin,
     StartScreen,
+    Settings,
     Quit,
-    Restart
+    Restart,
+    Analysis,
+    Review,
+    Setup,
+    Lobby,
+    Rooms,
 }
 
-use PieceType::*;
+// `posx`/`posy` are in squares and may be fractional while a piece is sliding
+fn draw_piece(spritesheet: &Texture2D, piece: Piece, posx: f32, posy: f32) {
+    draw_piece_sized(spritesheet, piece, posx * BLOCKSIZE, posy * BLOCKSIZE, BLOCKSIZE);
+}
 
-fn draw_piece(spritesheet: &Texture2D, piecetype: &PieceType, posx: usize, posy: usize) {
-    let (coordinate_x, coordinate_y) = match piecetype {
-        WK => (0.0, 0.0),
-        BK => (0.0, 1.0),
-        WQ => (1.0, 0.0),
-        BQ => (1.0, 1.0),
-        WB => (2.0, 0.0),
-        BB => (2.0, 1.0),
-        WN => (3.0, 0.0),
-        BN => (3.0, 1.0),
-        WR => (4.0, 0.0),
-        BR => (4.0, 1.0),
-        W => (5.0, 0.0),
-        B => (5.0, 1.0),
-        E => (6.0, 6.0),
-    };
-    if coordinate_x != 6.0 {
-        draw_texture_ex(
-            spritesheet,
-            posx as f32 * BLOCKSIZE,
-            posy as f32 * BLOCKSIZE,
-            WHITE,
-            DrawTextureParams {
-                source: Some(Rect::new(
-                    coordinate_x * SPRITESIZE,
-                    coordinate_y * SPRITESIZE,
-                    SPRITESIZE,
-                    SPRITESIZE,
-                )),
-                dest_size: Some(Vec2::new(BLOCKSIZE, BLOCKSIZE)),
-                ..Default::default()
-            },
-        );
+// the same in pixels, for pieces drawn off the board
+fn draw_piece_sized(spritesheet: &Texture2D, piece: Piece, x: f32, y: f32, size: f32) {
+    // columns follow `Role`, white pieces are on the top row; sheets are two sprites tall
+    // and usually stop after the pawn, so fairy pieces may have no sprite of their own
+    let coordinate_x = piece.role as usize as f32;
+    let coordinate_y = piece.color as usize as f32;
+    let sprite_size = spritesheet.height() / 2.0;
+    if (coordinate_x + 1.0) * sprite_size > spritesheet.width() {
+        draw_fallback_piece(piece, x, y, size);
+        return;
     }
+    draw_texture_ex(
+        spritesheet,
+        x,
+        y,
+        WHITE,
+        DrawTextureParams {
+            source: Some(Rect::new(
+                coordinate_x * sprite_size,
+                coordinate_y * sprite_size,
+                sprite_size,
+                sprite_size,
+            )),
+            dest_size: Some(Vec2::new(size, size)),
+            ..Default::default()
+        },
+    );
 }
 
-struct Board{
-    width: usize,
-    height: usize,
-    pieces: Vec<Vec<PieceType>>,
-    spritesheet: Texture2D,
-    white_king_x: usize,
-    white_king_y: usize,
-    black_king_x: usize,
-    black_king_y: usize,
-    is_white_king_moved:bool,
-    is_black_king_moved:bool,
-    is_left_white_rook_moved:bool,
-    is_right_white_rook_moved:bool,
-    is_left_black_rook_moved:bool,
-    is_right_black_rook_moved:bool
+// a disc in the piece's colour with its letter on it
+fn draw_fallback_piece(piece: Piece, x: f32, y: f32, size: f32) {
+    let (fill, ink) = match piece.color {
+        Side::White => (WHITE, BLACK),
+        Side::Black => (BLACK, WHITE),
+    };
+    let (center_x, center_y) = (x + size / 2.0, y + size / 2.0);
+    draw_circle(center_x, center_y, size * 0.4, fill);
+    draw_circle_lines(center_x, center_y, size * 0.4, size * 0.04, ink);
+    let letter = piece.role.letter().to_string();
+    let font_size = size * 0.6;
+    let dimensions = measure_text(&letter, None, font_size as u16, 1.0);
+    draw_text(
+        &letter,
+        center_x - dimensions.width / 2.0,
+        center_y + dimensions.offset_y / 2.0,
+        font_size,
+        ink,
+    );
 }
 
-impl Board {
-    fn new(sprite_sheet: Texture2D) -> Self{
-        Board {
-            width: 8,
-            height: 8,
-            spritesheet: sprite_sheet,
-            pieces: vec![
-                vec![BR, BN, BB, BQ, BK, BB, BN, BR],
-                vec![B, B, B, B, B, B, B, B],
-                vec![E, E, E, E, E, E, E, E],
-                vec![E, E, E, E, E, E, E, E],
-                vec![E, E, E, E, E, E, E, E],
-                vec![E, E, E, E, E, E, E, E],
-                vec![W, W, W, W, W, W, W, W],
-                vec![WR, WN, WB, WQ, WK, WB, WN, WR],
-            ],
-            white_king_x: 4,
-            white_king_y: 7,
-            black_king_x: 4,
-            black_king_y: 0,
-            is_white_king_moved:false,
-            is_black_king_moved:false,
-            is_left_white_rook_moved:false,
-            is_right_white_rook_moved:false,
-            is_left_black_rook_moved:false,
-            is_right_black_rook_moved:false
-            
-        }
+fn square_color(theme: Theme, x: usize, y: usize) -> Color {
+    match (x + y) % 2 {
+        0 => theme.light(),
+        _ => theme.dark(),
     }
-    fn draw(&self) {
-        for i in 0..self.width {
-            for j in 0..self.height {
-                let color = match (i + j) % 2 {
-                    0 => BLUE,
-                    1 => SKYBLUE,
-                    _ => BLUE,
-                };
-                draw_rectangle(
-                    i as f32 * BLOCKSIZE,
-                    j as f32 * BLOCKSIZE,
-                    BLOCKSIZE,
-                    BLOCKSIZE,
-                    color,
-                );
-                draw_piece(&self.spritesheet, &self.pieces[j][i], i, j);
+}
+// with `flipped` black's back rank is drawn at the bottom
+fn draw_board(board: &Board, spritesheet: &Texture2D, theme: Theme, flipped: bool) {
+    for i in 0..board.width {
+        for j in 0..board.height {
+            let (screen_x, screen_y) = if flipped {
+                (board.width - 1 - i, board.height - 1 - j)
+            } else {
+                (i, j)
+            };
+            draw_rectangle(
+                screen_x as f32 * BLOCKSIZE,
+                screen_y as f32 * BLOCKSIZE,
+                BLOCKSIZE,
+                BLOCKSIZE,
+                square_color(theme, i, j),
+            );
+            if let Some(piece) = board.piece_at(i, j) {
+                draw_piece(spritesheet, piece, screen_x as f32, screen_y as f32);
             }
         }
     }
-    fn is_black_piece(&self, x: usize, y: usize) -> bool {
-        matches!(self.pieces[y][x], B | BK | BQ | BR | BN | BB)
+}
+
+fn detect_mouse() -> (usize, usize) {
+    if is_mouse_button_pressed(MouseButton::Left) {
+        let (x, y) = mouse_position();
+        let i: usize = (x / BLOCKSIZE) as usize;
+        let j: usize = (y / BLOCKSIZE) as usize;
+        return (i, j);
     }
+    (usize::MAX, usize::MAX)
+}
 
-    fn is_white_piece(&self, x: usize, y: usize) -> bool {
-        matches!(self.pieces[y][x], W | WK | WQ | WR | WN | WB)
+// an arrow between the centres of two screen squares
+fn draw_arrow(from: (usize, usize), to: (usize, usize), color: Color) {
+    let center = |(x, y): (usize, usize)| vec2((x as f32 + 0.5) * BLOCKSIZE, (y as f32 + 0.5) * BLOCKSIZE);
+    let (start, end) = (center(from), center(to));
+    // a drop has no square to come from, so its square is ringed instead
+    if from == to {
+        draw_circle_lines(end.x, end.y, BLOCKSIZE * 0.4, BLOCKSIZE * 0.08, color);
+        return;
     }
+    let direction = (end - start).normalize();
+    let head = end - direction * BLOCKSIZE * 0.3;
+    let side = vec2(-direction.y, direction.x) * BLOCKSIZE * 0.2;
+    draw_line(start.x, start.y, head.x, head.y, BLOCKSIZE * 0.15, color);
+    draw_triangle(end, head + side, head - side, color);
+}
 
-    fn is_white_king_in_check(&self, x: usize, y: usize) -> bool {
-        // straight line check
-        for i in (0..x).rev() {
-            if self.pieces[y][i] != E {
-                if self.pieces[y][i] == BR || self.pieces[y][i] == BQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
-        for i in x + 1..self.width {
-            if self.pieces[y][i] != E {
-                if self.pieces[y][i] == BR || self.pieces[y][i] == BQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
-        for i in (0..y).rev() {
-            if self.pieces[i][x] != E {
-                if self.pieces[i][x] == BR || self.pieces[i][x] == BQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
-        for i in y + 1..self.height {
-            if self.pieces[i][x] != E {
-                if self.pieces[i][x] == BR || self.pieces[i][x] == BQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
+// why the engine was asked, a hint remembers the position it was asked about
+#[derive(Clone, Copy)]
+enum SearchPurpose {
+    Move,
+    Hint(Hash),
+    Analysis,
+}
 
-        // diagonal cheking
+// facts about the game besides its moves
+#[derive(Default)]
+struct Metadata {
+    // hints asked for, indexed by colour
+    hints: [u32; 2],
+    // the Chess960 start position's number, so both players can set it up elsewhere
+    chess960: Option<u32>,
+    // why the game ended, shown with the result
+    end_reason: Option<String>,
+}
 
-        let mut curr_x = (x + 1) as i32;
-        let mut curr_y = (y + 1) as i32;
+// a piece sliding from `from` to `to`, both in board coordinates
+struct Animation {
+    piece: Piece,
+    from: (usize, usize),
+    to: (usize, usize),
+    elapsed: f32,
+}
 
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == BB
-                || self.pieces[curr_y as usize][curr_x as usize] == BQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x += 1;
-            curr_y += 1;
-        }
-        let mut curr_x = (x - 1) as i32;
-        let mut curr_y = (y + 1) as i32;
+// the blast of an Atomic capture, centred on the square taken on
+struct Explosion {
+    center: (usize, usize),
+    elapsed: f32,
+}
 
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == BB
-                || self.pieces[curr_y as usize][curr_x as usize] == BQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x -= 1;
-            curr_y += 1;
-        }
-        let mut curr_x = (x + 1) as i32;
-        let mut curr_y = (y - 1) as i32;
+const EXPLOSION_TIME: f32 = 0.4;
 
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == BB
-                || self.pieces[curr_y as usize][curr_x as usize] == BQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x += 1;
-            curr_y -= 1;
-        }
-        let mut curr_x = (x - 1) as i32;
-        let mut curr_y = (y - 1) as i32;
+struct Game {
+    turn: Side,
+    board: Board,
+    spritesheet: Texture2D,
+    selected: bool,
+    selected_x: usize,
+    selected_y: usize,
+    legal_moves: Vec<(usize, usize)>,
+    // a piece picked from the pocket in Crazyhouse, its targets are in `legal_moves`
+    selected_drop: Option<Role>,
+    game_condition:GameCondition,
+    settings: Settings,
+    piece_sets: PieceSets,
+    asset_error: Option<String>,
+    clock: Option<Clock>,
+    animation: Option<Animation>,
+    explosion: Option<Explosion>,
+    move_input: String,
+    input_error: Option<String>,
+    cursor: Option<(usize, usize)>,
+    // lent to the search thread while the computer thinks
+    engine: Option<Engine>,
+    thinking: Option<(SearchPurpose, JoinHandle<(Engine, SearchInfo)>)>,
+    stop: Arc<AtomicBool>,
+    search_info: Option<SearchInfo>,
+    hint: Option<Move>,
+    metadata: Metadata,
+    // the position the game started from and every move played since
+    start: Board,
+    moves: Vec<(Move, Undo)>,
+    analysis: Analysis,
+    review: Option<ReviewScreen>,
+    setup: Option<Setup>,
+    lobby: Option<Lobby>,
+    rooms: Option<Rooms>,
+    online: Option<Online>,
+    // what became of the last attempt to save or load a file
+    file_status: Option<String>,
+    // a local game left for the start screen, to be resumed where it was
+    paused: bool,
+}
 
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == BB
-                || self.pieces[curr_y as usize][curr_x as usize] == BQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x -= 1;
-            curr_y -= 1;
+impl Game {
+    fn new(piece_sets: PieceSets, settings: Settings) -> Self {
+        let (spritesheet, asset_error) = piece_sets.load(&settings.piece_set);
+        let engine = Engine::new();
+        let variant = settings.variant();
+        let mut start = Board::start_position(variant);
+        // Chess960 shuffles the standard pieces, so it needs the standard board
+        let chess960 = (settings.chess960 && start.width == 8 && start.height == 8).then(board::random_chess960_index);
+        if let Some(index) = chess960 {
+            start = Board::chess960(index);
+            start.set_variant(variant);
         }
-
-        // for the knight
-
-        let diffs = [
-            (2, 1),
-            (-2, 1),
-            (2, -1),
-            (-2, -1),
-            (1, 2),
-            (-1, 2),
-            (1, -2),
-            (-1, -2),
-        ];
-        let curr_x = x as i32;
-        let curr_y = y as i32;
-
-        for diff in diffs {
-            if curr_x + diff.0 < self.width as i32
-            && curr_x + diff.0 >= 0
-            && curr_y + diff.1 < self.height as i32
-            && curr_y + diff.1 >= 0
-            && self.pieces[(curr_y + diff.1) as usize][(curr_x + diff.0) as usize] == BN
-            {
-                return true;
-            }
+        request_new_screen_size(start.width as f32 * BLOCKSIZE + PANEL_WIDTH, WINDOW_HEIGHT);
+        Game {
+            turn: Side::White,
+            board: start.clone(),
+            spritesheet,
+            selected: false,
+            selected_x: 0,
+            selected_y: 0,
+            legal_moves: Vec::new(),
+            selected_drop: None,
+            game_condition:GameCondition::StartScreen,
+            clock: Clock::new(settings.time_control),
+            animation: None,
+            explosion: None,
+            move_input: String::new(),
+            input_error: None,
+            cursor: None,
+            stop: engine.stop_flag(),
+            engine: Some(engine),
+            thinking: None,
+            search_info: None,
+            hint: None,
+            metadata: Metadata { chess960, ..Metadata::default() },
+            start,
+            moves: Vec::new(),
+            analysis: Analysis::default(),
+            review: None,
+            setup: None,
+            lobby: None,
+            rooms: None,
+            online: None,
+            file_status: None,
+            paused: false,
+            settings,
+            piece_sets,
+            asset_error,
         }
+    }
 
-        // king check
-        let diffs = [
-            (1, 1),
-            (-1, 1),
-            (1, -1),
-            (-1, -1),
-            (1, 0),
-            (-1, 0),
-            (0, -1),
-            (0, 1),
-        ];
-
-        for diff in diffs {
-            if curr_x + diff.0 < self.width as i32
-            && curr_x + diff.0 >= 0
-            && curr_y + diff.1 < self.height as i32
-            && curr_y + diff.1 >= 0
-            && self.pieces[(curr_y + diff.1) as usize][(curr_x + diff.0) as usize] == BK
-            {
-                return true;
-            }
+    // against the computer the human's side stays at the bottom
+    fn is_flipped(&self) -> bool {
+        if let Some(online) = &self.online {
+            return online.color == Some(Side::Black);
+        }
+        match self.settings.opponent.engine_side() {
+            Some(engine_side) => engine_side == Side::White,
+            None => self.settings.auto_flip && self.turn == Side::Black,
         }
+    }
 
-        // pawn check
+    fn is_engine_turn(&self) -> bool {
+        self.online.is_none()
+            && !matches!(self.game_condition, GameCondition::Analysis) && self.settings.opponent.engine_side() == Some(self.turn)
+    }
 
-        if (curr_y - 1) >= 0
-        && (((curr_x - 1) >= 0
-        && self.pieces[(curr_y - 1) as usize][(curr_x - 1) as usize] == B)
-        || curr_x + 1 < self.width as i32
-        && self.pieces[(curr_y - 1) as usize][(curr_x + 1) as usize] == B)
-        {
-            return true;
+    // maps board coordinates to screen squares and back, flipping is its own inverse
+    fn screen_square(&self, x: usize, y: usize) -> (usize, usize) {
+        if self.is_flipped() {
+            (self.board.width - 1 - x, self.board.height - 1 - y)
+        } else {
+            (x, y)
         }
-        false
     }
-    fn is_black_king_in_check(&self, x: usize, y: usize) -> bool {
-        // straight line check
-        for i in (0..x).rev() {
-            if self.pieces[y][i] != E {
-                if self.pieces[y][i] == WR || self.pieces[y][i] == WQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
-        for i in x + 1..self.width {
-            if self.pieces[y][i] != E {
-                if self.pieces[y][i] == WR || self.pieces[y][i] == WQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
-        for i in (0..y).rev() {
-            if self.pieces[i][x] != E {
-                if self.pieces[i][x] == WR || self.pieces[i][x] == WQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
-        for i in y + 1..self.height {
-            if self.pieces[i][x] != E {
-                if self.pieces[i][x] == WR || self.pieces[i][x] == WQ {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-        }
 
-        // diagonal cheking
+    fn cycle_theme(&mut self) {
+        self.settings.theme = self.settings.theme.next();
+        let _ = self.settings.save();
+    }
 
-        let mut curr_x = (x + 1) as i32;
-        let mut curr_y = (y + 1) as i32;
+    // cycles bundled sheet -> every sheet in the pieces folder -> bundled sheet
+    fn cycle_piece_set(&mut self) {
+        let next_index = match &self.settings.piece_set {
+            None => 0,
+            Some(name) => match self.piece_sets.names.iter().position(|set| set == name) {
+                Some(index) => index + 1,
+                None => 0,
+            },
+        };
+        self.settings.piece_set = self.piece_sets.names.get(next_index).cloned();
+        (self.spritesheet, self.asset_error) = self.piece_sets.load(&self.settings.piece_set);
+        let _ = self.settings.save();
+    }
 
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == WB
-                || self.pieces[curr_y as usize][curr_x as usize] == WQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x += 1;
-            curr_y += 1;
-        }
-        let mut curr_x = x as i32 - 1;
-        let mut curr_y = (y + 1) as i32;
+    fn deselect_and_clear_legal_moves(&mut self) {
+        self.selected = false;
+        self.selected_drop = None;
+        self.legal_moves.clear();
+    }
+    fn change_selected_and_fetch_legal_moves(&mut self, x: usize, y: usize) {
+        self.selected=true;
+        self.selected_x = x;
+        self.selected_y = y;
 
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == WB
-                || self.pieces[curr_y as usize][curr_x as usize] == WQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x -= 1;
-            curr_y += 1;
-        }
-        let mut curr_x = (x + 1) as i32;
-        let mut curr_y = y as i32 - 1;
+        self.legal_moves = self.board.get_legal_moves_for_piece(x, y);
+    }
+    // `promotion` is only used by pawns reaching the last rank, which otherwise become queens
+    fn move_piece(&mut self, x: usize, y: usize, promotion: Option<Role>) -> Move {
+        let played = self.board.move_piece(self.selected_x, self.selected_y, x, y, promotion);
+        self.moves.push(played);
+        played.0
+    }
 
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == WB
-                || self.pieces[curr_y as usize][curr_x as usize] == WQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x += 1;
-            curr_y -= 1;
+    // holding the right button over a square outlines every piece attacking it
+    fn highlight_attackers(&self) {
+        if !is_mouse_button_down(MouseButton::Right) {
+            return;
         }
-        let mut curr_x = x as i32 - 1;
-        let mut curr_y = y as i32 - 1;
-
-        while curr_x < (self.width as i32)
-        && curr_y < (self.height as i32)
-        && curr_y >= 0
-        && curr_x >= 0
-        {
-            if self.pieces[curr_y as usize][curr_x as usize] != E {
-                if self.pieces[curr_y as usize][curr_x as usize] == WB
-                || self.pieces[curr_y as usize][curr_x as usize] == WQ
-                {
-                    return true;
-                } else {
-                    break;
-                }
-            }
-            curr_x -= 1;
-            curr_y -= 1;
+        let (mouse_x, mouse_y) = mouse_position();
+        let (x, y) = ((mouse_x / BLOCKSIZE) as usize, (mouse_y / BLOCKSIZE) as usize);
+        if x >= self.board.width || y >= self.board.height {
+            return;
         }
-
-        // for the knight
-
-        let diffs = [
-            (2, 1),
-            (-2, 1),
-            (2, -1),
-            (-2, -1),
-            (1, 2),
-            (-1, 2),
-            (1, -2),
-            (-1, -2),
-        ];
-        let curr_x = x as i32;
-        let curr_y = y as i32;
-
-        for diff in diffs {
-            if curr_x + diff.0 < self.width as i32
-            && curr_x + diff.0 >= 0
-            && curr_y + diff.1 < self.height as i32
-            && curr_y + diff.1 >= 0
-            && self.pieces[(curr_y + diff.1) as usize][(curr_x + diff.0) as usize] == WN
-            {
-                return true;
-            }
+        let (x, y) = self.screen_square(x, y);
+        for attacker in squares(self.board.attackers_of(square(x, y))) {
+            let (attacker_x, attacker_y) = (square_x(attacker), square_y(attacker));
+            let color = match self.board.color_at(attacker_x, attacker_y) {
+                Some(Side::White) => ORANGE,
+                _ => RED,
+            };
+            let (attacker_x, attacker_y) = self.screen_square(attacker_x, attacker_y);
+            draw_rectangle_lines(
+                attacker_x as f32 * BLOCKSIZE + BUFF,
+                attacker_y as f32 * BLOCKSIZE + BUFF,
+                BLOCKSIZE - 2.0 * BUFF,
+                BLOCKSIZE - 2.0 * BUFF,
+                2.0 * BUFF,
+                color,
+            );
         }
+    }
 
-        // king check
-        let diffs = [
-            (1, 1),
-            (-1, 1),
-            (1, -1),
-            (-1, -1),
-            (1, 0),
-            (-1, 0),
-            (0, -1),
-            (0, 1),
-        ];
-
-        for diff in diffs {
-            if curr_x + diff.0 < self.width as i32
-            && curr_x + diff.0 >= 0
-            && curr_y + diff.1 < self.height as i32
-            && curr_y + diff.1 >= 0
-            && self.pieces[(curr_y + diff.1) as usize][(curr_x + diff.0) as usize] == WK
-            {
-                return true;
-            }
+    fn highlight_legal_moves(&self) {
+        for &(x, y) in &self.legal_moves {
+            let (x, y) = self.screen_square(x, y);
+            draw_rectangle(x as f32 * BLOCKSIZE, y as f32*BLOCKSIZE, BLOCKSIZE, BLOCKSIZE, Color::new(0.0, 0.0, 0.0, 0.2));
         }
+    }
 
-        // pawn check
-
-        if (curr_y + 1) < self.height as i32
-        && (((curr_x - 1) >= 0
-        && self.pieces[(curr_y + 1) as usize][(curr_x - 1) as usize] == W)
-        || (curr_x + 1 < self.width as i32
-        && self.pieces[(curr_y + 1) as usize][(curr_x + 1) as usize] == W))
-        {
-            return true;
+    // hides the piece on its destination square and draws it part way along its path instead
+    fn draw_animation(&mut self) {
+        let Some(animation) = &mut self.animation else {
+            return;
+        };
+        animation.elapsed += get_frame_time();
+        let duration = self.settings.animation_speed.duration();
+        if animation.elapsed >= duration {
+            self.animation = None;
+            return;
         }
-        false
+        let (to_x, to_y) = animation.to;
+        let (from_x, from_y) = animation.from;
+        let piece = animation.piece;
+        let progress = animation.elapsed / duration;
+
+        let (screen_to_x, screen_to_y) = self.screen_square(to_x, to_y);
+        let (screen_from_x, screen_from_y) = self.screen_square(from_x, from_y);
+        draw_rectangle(
+            screen_to_x as f32 * BLOCKSIZE,
+            screen_to_y as f32 * BLOCKSIZE,
+            BLOCKSIZE,
+            BLOCKSIZE,
+            square_color(self.settings.theme, to_x, to_y),
+        );
+        draw_piece(
+            &self.spritesheet,
+            piece,
+            screen_from_x as f32 + (screen_to_x as f32 - screen_from_x as f32) * progress,
+            screen_from_y as f32 + (screen_to_y as f32 - screen_from_y as f32) * progress,
+        );
     }
 
-    fn is_legal_move(&mut self, src_x: usize, src_y: usize, dist_x: usize, dist_y: usize) -> bool {
-        let removed_piece = self.pieces[dist_y][dist_x];
-        let current_piece = self.pieces[src_y][src_x];
-
-        if self.is_black_piece(src_x, src_y) && self.is_black_piece(dist_x, dist_y) {
-            return false;
+    // starts once the capturing piece has arrived, a ring of fire spreading over the 3x3 area
+    fn draw_explosion(&mut self) {
+        if self.animation.is_some() {
+            return;
         }
-        if self.is_white_piece(src_x, src_y) && self.is_white_piece(dist_x, dist_y) {
-            return false;
+        let Some(explosion) = &mut self.explosion else {
+            return;
+        };
+        explosion.elapsed += get_frame_time();
+        if explosion.elapsed >= EXPLOSION_TIME {
+            self.explosion = None;
+            return;
         }
+        let progress = explosion.elapsed / EXPLOSION_TIME;
+        let (x, y) = explosion.center;
+        let (x, y) = self.screen_square(x, y);
+        let (center_x, center_y) = ((x as f32 + 0.5) * BLOCKSIZE, (y as f32 + 0.5) * BLOCKSIZE);
+        let fade = 1.0 - progress;
+        draw_circle(center_x, center_y, BLOCKSIZE * 1.5 * progress, Color::new(1.0, 0.6, 0.1, 0.5 * fade));
+        draw_circle(center_x, center_y, BLOCKSIZE * 0.8 * progress, Color::new(1.0, 0.9, 0.3, 0.7 * fade));
+    }
 
-        if self.is_black_piece(src_x, src_y) {
-            self.pieces[dist_y][dist_x] = current_piece;
-            self.pieces[src_y][src_x] = E;
-            if current_piece == BK {
-                self.black_king_x = dist_x;
-                self.black_king_y = dist_y;
-            }
-
-            if self.is_black_king_in_check(self.black_king_x, self.black_king_y) {
-                self.pieces[src_y][src_x] = current_piece;
-                self.pieces[dist_y][dist_x] = removed_piece;
-                if current_piece == BK {
-                    self.black_king_x = src_x;
-                    self.black_king_y = src_y;
-                }
-                return false;
-            }
-            if current_piece == BK {
-                self.black_king_x = src_x;
-                self.black_king_y = src_y;
-            }
+    // the side to move sits at the bottom, so their clock goes below the other one
+    fn draw_clock(&self) {
+        let Some(clock) = &self.clock else {
+            return;
+        };
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let (top, bottom) = if self.is_flipped() {
+            (Side::White, Side::Black)
+        } else {
+            (Side::Black, Side::White)
+        };
+        for (turn, y) in [(top, 40.0), (bottom, self.board.height as f32 * BLOCKSIZE - 20.0)] {
+            let color = if turn == self.turn { WHITE } else { GRAY };
+            draw_text(&clock.format(turn), x, y, 40.0, color);
         }
-        if self.is_white_piece(src_x, src_y) {
-            self.pieces[dist_y][dist_x] = current_piece;
-            self.pieces[src_y][src_x] = E;
-            if current_piece == WK {
-                self.white_king_x = dist_x;
-                self.white_king_y = dist_y;
-            }
-
-            if self.is_white_king_in_check(self.white_king_x, self.white_king_y) {
-                self.pieces[src_y][src_x] = current_piece;
-                self.pieces[dist_y][dist_x] = removed_piece;
-                if current_piece == WK {
-                    self.white_king_x = src_x;
-                    self.white_king_y = src_y;
-                }
-                return false;
-            }
-            if current_piece == WK {
-                self.white_king_x = src_x;
-                self.white_king_y = src_y;
-            }
+    }
+    // three-check is won on the third, so keep the count in view
+    fn draw_checks(&self) {
+        if !self.board.variant().counts_checks() {
+            return;
         }
-        self.pieces[src_y][src_x] = current_piece;
-        self.pieces[dist_y][dist_x] = removed_piece;
-
-        true
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let checks = self.board.checks();
+        let text = format!("checks: White {}, Black {}", checks[0], checks[1]);
+        draw_text(&text, x, 120.0, 18.0, WHITE);
     }
+    fn run(&mut self) {
+        self.poll_online();
+        if !matches!(self.game_condition, GameCondition::Running) {
+            return;
+        }
 
-    fn get_legal_moves_for_piece(
-        &mut self,
-        src_x: usize,
-        src_y: usize,
-    ) -> Vec<(usize, usize)> {
-        let mut legal_moves: Vec<(usize, usize)> = Vec::with_capacity(MAX_POSSIBLE_LEGAL_MOVES);
-        let curr_x = src_x as i32;
-        let curr_y = src_y as i32;
-        match self.pieces[src_y][src_x] {
-            WK | BK => {
-                let diffs = [
-                    (1, 1),
-                    (-1, 1),
-                    (1, -1),
-                    (-1, -1),
-                    (1, 0),
-                    (-1, 0),
-                    (0, -1),
-                    (0, 1),
-                ];
-
-                for diff in diffs {
-                    let dest_x: i32 = curr_x + diff.0;
-                    let dest_y: i32 = curr_y + diff.1;
-                    if dest_x < self.width as i32
-                    && dest_x >= 0
-                    && dest_y < self.height as i32
-                    && dest_y >= 0
-                    && self.is_legal_move(
-                        curr_x as usize,
-                        curr_y as usize,
-                        dest_x as usize,
-                        dest_y as usize,
-                    )
-                    {
-                        legal_moves.push((dest_x as usize, dest_y as usize));
-                    }
-                }
-
-                if self.pieces[src_y][src_x] == WK &&
-                src_y == self.width-1 && src_x == 4 &&
-                    !self.is_white_king_moved{
-                        
-                    if !self.is_right_white_rook_moved &&
-                        self.pieces[src_y][5]==E&&
-                        self.pieces[src_y][6]==E &&
-        self.is_legal_move(src_x, src_y, 6, src_y)&&
-        self.is_legal_move(src_x, src_y, 5, src_y){
-                            legal_moves.push((6,src_y)) ;
-                    }
-                    if !self.is_left_white_rook_moved  &&
-                        self.pieces[src_y][3] ==E &&
-                        self.pieces[src_y][2] ==E &&
-                        self.is_legal_move(src_x, src_y, 3, src_y)&&
-                        self.is_legal_move(src_x, src_y, 2, src_y){
-                            legal_moves.push((2,src_y)) ;
-                    }
-                    
-                }
-                if self.pieces[src_y][src_x]==BK&&
-                src_y == 0 && src_x == 4 &&
-                    !self.is_black_king_moved{
-                    if !self.is_right_black_rook_moved &&
-                        self.pieces[src_y][5]==E&&
-                        self.pieces[src_y][6]==E &&
-        self.is_legal_move(src_x, src_y, 6, src_y)&&
-        self.is_legal_move(src_x, src_y, 5, src_y){
-                            legal_moves.push((6,src_y)) ;
-                    }
-                    if !self.is_left_black_rook_moved  &&
-                        self.pieces[src_y][3] ==E &&
-                        self.pieces[src_y][2] ==E &&
-                        self.is_legal_move(src_x, src_y, 3, src_y)&&
-                        self.is_legal_move(src_x, src_y, 2, src_y){
-                            legal_moves.push((2,src_y)) ;
-                    }
-
-                }
-            }
-            WN | BN => {
-                let diffs = [
-                    (2, 1),
-                    (-2, 1),
-                    (2, -1),
-                    (-2, -1),
-                    (1, 2),
-                    (-1, 2),
-                    (1, -2),
-                    (-1, -2),
-                ];
-
-                for diff in diffs {
-                    let dest_x: i32 = curr_x + diff.0;
-                    let dest_y: i32 = curr_y + diff.1;
-                    if dest_x < self.width as i32
-                    && dest_x >= 0
-                    && dest_y < self.height as i32
-                    && dest_y >= 0
-                    && self.is_legal_move(
-                        curr_x as usize,
-                        curr_y as usize,
-                        dest_x as usize,
-                        dest_y as usize,
-                    )
-                    {
-                        legal_moves.push((dest_x as usize, dest_y as usize));
-                    }
-                }
-            }
-            WR | BR => {
-                for i in (0..src_x).rev() {
-                    if self.is_legal_move(src_x, src_y, i, src_y) {
-                        legal_moves.push((i, src_y));
-                    }
-                    if self.pieces[src_y][i] != E {
-                        break;
-                    }
-                }
-                for i in src_y + 1..self.width {
-                    if self.is_legal_move(src_x, src_y, i, src_y) {
-                        legal_moves.push((i, src_y));
-                    }
-                    if self.pieces[src_y][i] != E {
-                        break;
-                    }
-                }
-                for j in (0..src_y).rev() {
-                    if self.is_legal_move(src_x, src_y, src_x, j) {
-                        legal_moves.push((src_x, j));
-                    }
-                    if self.pieces[j][src_x] != E {
-                        break;
-                    }
-                }
-                for j in src_y + 1..self.width {
-                    if self.is_legal_move(src_x, src_y, src_x, j) {
-                        legal_moves.push((src_x, j));
-                    }
-                    if self.pieces[j][src_x] != E {
-                        break;
-                    }
-                }
-            }
-            WB | BB => {
-                let move_dirs = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
-
-                for move_dir in move_dirs {
-                    let mut dest_x = src_x as i32 + move_dir.0;
-                    let mut dest_y = src_y as i32 + move_dir.1;
-                    while dest_x >= 0
-                    && dest_x < self.width as i32
-                    && dest_y >= 0
-                    && dest_y < self.height as i32
-                    {
-                        if self.is_legal_move(src_x, src_y, dest_x as usize, dest_y as usize) {
-                            legal_moves.push((dest_x as usize, dest_y as usize));
-                        }
-                        if self.pieces[dest_y as usize][dest_x as usize] != E {
-                            break;
-                        }
-                        dest_x += move_dir.0;
-                        dest_y += move_dir.1;
-                    }
-                }
+        if let Some(clock) = &mut self.clock {
+            clock.tick(self.turn, get_frame_time());
+            // a server keeps the real clocks and says when someone has run out of time
+            let server_decides = self.online.as_ref().is_some_and(|online| online.server);
+            if clock.is_flagged(self.turn) && !server_decides {
+                self.game_condition = match self.turn {
+                    Side::White => GameCondition::BlackWin,
+                    Side::Black => GameCondition::WhiteWin,
+                };
+                self.metadata.end_reason = Some("time".to_string());
+                return;
             }
-            WQ | BQ => {
-                for i in (0..src_x).rev() {
-                    if self.is_legal_move(src_x, src_y, i, src_y) {
-                        legal_moves.push((i, src_y));
-                    }
-                    if self.pieces[src_y][i] != E {
-                        break;
-                    }
-                }
-                for i in src_x + 1..self.width {
-                    if self.is_legal_move(src_x, src_y, i, src_y) {
-                        legal_moves.push((i, src_y));
-                    }
-                    if self.pieces[src_y][i] != E {
-                        break;
-                    }
-                }
-                for j in (0..src_y).rev() {
-                    if self.is_legal_move(src_x, src_y, src_x, j) {
-                        legal_moves.push((src_x, j));
-                    }
-                    if self.pieces[j][src_x] != E {
-                        break;
-                    }
-                }
-                for j in src_y + 1..self.width {
-                    if self.is_legal_move(src_x, src_y, src_x, j) {
-                        legal_moves.push((src_x, j));
-                    }
-                    if self.pieces[j][src_x] != E {
-                        break;
-                    }
-                }
+        }
 
-                let move_dirs = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+        // the variant decides what ends the game and why
+        if let Some(end) = self.board.game_end() {
+            self.game_condition = match end.winner {
+                Some(Side::White) => GameCondition::WhiteWin,
+                Some(Side::Black) => GameCondition::BlackWin,
+                None => GameCondition::Draw,
+            };
+            self.metadata.end_reason = Some(end.reason.to_string());
+        }
 
-                for move_dir in move_dirs {
-                    let mut dest_x = src_x as i32 + move_dir.0;
-                    let mut dest_y = src_y as i32 + move_dir.1;
-                    while dest_x >= 0
-                    && dest_x < self.width as i32
-                    && dest_y >= 0
-                    && dest_y < self.height as i32
-                    {
-                        if self.is_legal_move(src_x, src_y, dest_x as usize, dest_y as usize) {
-                            legal_moves.push((dest_x as usize, dest_y as usize));
-                        }
-                        if self.pieces[dest_y as usize][dest_x as usize] != E {
-                            break;
-                        }
-                        dest_x += move_dir.0;
-                        dest_y += move_dir.1;
+        if let Some((purpose, info)) = self.finished_search() {
+            match purpose {
+                SearchPurpose::Move => {
+                    if let Some(mv) = info.best_move() {
+                        self.play_move(mv);
                     }
                 }
+                // the position may have moved on while the hint was being worked out
+                SearchPurpose::Hint(hash) if hash == self.board.hash() => self.hint = info.best_move(),
+                SearchPurpose::Hint(_) | SearchPurpose::Analysis => {}
             }
-            W => {
-                // one move forward
-
-                if self.pieces[src_y - 1][src_x] == E
-                && self.is_legal_move(src_x, src_y, src_x, src_y-1)
-                {
-                    legal_moves.push((src_x, src_y - 1));
-                }
-                // two move forward
-                if src_y == 6
-                && self.pieces[src_y - 1][src_x] == E
-                && self.pieces[src_y - 2][src_x] == E
-                && self.is_legal_move(src_x, src_y, src_x, src_y-2)
-                {
-                    legal_moves.push((src_x, src_y - 2));
-                }
-
-                // capture
+            self.search_info = Some(info);
+        }
+        let is_running = matches!(self.game_condition, GameCondition::Running);
+        if self.is_engine_turn() && is_running {
+            self.think();
+        }
 
-                if (curr_x - 1) >= 0
-                && self.is_black_piece(src_x - 1, src_y - 1)
-                && self.is_legal_move(src_x, src_y, src_x - 1, src_y - 1)
-                {
-                    legal_moves.push((src_x - 1, src_y - 1));
-                }
-                if (curr_x + 1) < self.width as i32
-                && self.is_black_piece(src_x + 1, src_y - 1)
-                && self.is_legal_move(src_x, src_y, src_x + 1, src_y - 1)
-                {
-                    legal_moves.push((src_x + 1, src_y - 1));
-                }
+        draw_board(&self.board, &self.spritesheet, self.settings.theme, self.is_flipped());
+        self.draw_animation();
+        self.draw_explosion();
+        self.draw_clock();
+        self.draw_checks();
+        self.draw_pockets();
+        self.draw_search_info();
+        self.draw_hint();
+        if is_running && self.online.is_some() {
+            self.draw_online_panel();
+            self.draw_chat_panel();
+        } else if is_running {
+            self.hint_button();
+            self.menu_button();
+        }
 
-                //TODO:: promotion
-                // promotion
-                // TODO: enpassant
-                // enpassant
-            }
-            B => {
-                if self.pieces[src_y + 1][src_x] == E
-                && self.is_legal_move(src_x, src_y, src_x, src_y+1)
-                {
-                    legal_moves.push((src_x, src_y + 1));
-                }
-                // two move forward
-                if src_y == 1
-                && self.pieces[src_y + 1][src_x] == E
-                && self.pieces[src_y + 2][src_x] == E
-                && self.is_legal_move(src_x, src_y,src_x , src_y + 2)
-                {
-                    legal_moves.push((src_x, src_y + 2));
-                }
+        if self.selected {
+            let (selected_x, selected_y) = self.screen_square(self.selected_x, self.selected_y);
+            draw_rectangle(
+                selected_x as f32 * BLOCKSIZE - BUFF,
+                selected_y as f32 * BLOCKSIZE - BUFF,
+                BLOCKSIZE + 2.0 * BUFF,
+                BLOCKSIZE + 2.0 * BUFF,
+                Color::new(0.0, 0.0, 0.0, 0.2),
+            );
+        }
 
-                // capture
+        if self.settings.show_legal_moves {
+            self.highlight_legal_moves();
+        }
+        self.highlight_attackers();
 
-                if (curr_x - 1) >= 0
-                && self.is_white_piece(src_x - 1, src_y + 1)
-                && self.is_legal_move(src_x, src_y, src_x - 1, src_y + 1)
-                {
-                    legal_moves.push((src_x - 1, src_y + 1));
-                }
-                if (curr_x + 1) < self.width as i32
-                && self.is_white_piece(src_x + 1, src_y + 1)
-                && self.is_legal_move(src_x, src_y, src_x + 1, src_y + 1)
-                {
-                    legal_moves.push((src_x + 1, src_y + 1));
-                }
+        self.draw_cursor();
+        self.draw_move_input();
 
-                //TODO:: promotion
-                // promotion
-                // TODO: enpassant
-                // enpassant
-            }
-            _ => {}
+        self.handle_pockets();
+        let (x, y) = detect_mouse();
+        if x != usize::MAX && y != usize::MAX && x < self.board.width && y < self.board.height {
+            let (x, y) = self.screen_square(x, y);
+            self.select_square(x, y);
         }
-
-        legal_moves
+        self.handle_keyboard();
     }
 
-    fn does_black_have_legal_moves(&mut self) -> bool{
+    // runs `search` on a copy of the board in the background, so the window keeps
+    // drawing while the computer thinks; does nothing while another search is running
+    fn start_search(
+        &mut self,
+        purpose: SearchPurpose,
+        search: impl FnOnce(&mut Engine, &mut Board) -> SearchInfo + Send + 'static,
+    ) {
+        let Some(mut engine) = self.engine.take() else {
+            return;
+        };
+        let moves: Vec<Move> = self.moves.iter().map(|&(mv, _)| mv).collect();
+        engine.set_game_history(engine::game_history(&self.start, &moves));
+        self.stop.store(false, Ordering::Relaxed);
+        let mut board = self.board.clone();
+        self.thinking = Some((
+            purpose,
+            thread::spawn(move || {
+                let info = search(&mut engine, &mut board);
+                (engine, info)
+            }),
+        ));
+    }
 
-        for i in 0..self.width{
-            for j in 0..self.height{
-                if self.is_black_piece(i,j){
-                    let legal_moves = self.get_legal_moves_for_piece(i,j);
-                    if !legal_moves.is_empty(){
-                        return true;
-                    }
-                }
-            }
-        }
-        false
+    // takes the engine back once its search is done
+    fn finished_search(&mut self) -> Option<(SearchPurpose, SearchInfo)> {
+        let (purpose, thinking) = self.thinking.take_if(|(_, thinking)| thinking.is_finished())?;
+        let (engine, info) = thinking.join().expect("the search thread panicked");
+        self.engine = Some(engine);
+        Some((purpose, info))
     }
-    fn does_white_have_legal_moves(&mut self) -> bool{
 
-        for i in 0..self.width{
-            for j in 0..self.height{
-                if self.is_white_piece(i,j){
-                    let legal_moves = self.get_legal_moves_for_piece(i,j);
-                    if !legal_moves.is_empty(){
-                        return true;
-                    }
-                }
-            }
+    // stops a search and throws its result away, it belongs to a position no longer on the board
+    fn cancel_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some((_, thinking)) = self.thinking.take() {
+            let (engine, _) = thinking.join().expect("the search thread panicked");
+            self.engine = Some(engine);
         }
-        false
     }
-}
 
-fn detect_mouse() -> (usize, usize) {
-    if is_mouse_button_pressed(MouseButton::Left) {
-        let (x, y) = mouse_position();
-        let i: usize = (x / BLOCKSIZE) as usize;
-        let j: usize = (y / BLOCKSIZE) as usize;
-        return (i, j);
+    fn think(&mut self) {
+        // a second a move, less when the clock is running low
+        let time = match &self.clock {
+            Some(clock) => (clock.remaining(self.turn) / 30.0).min(1.0),
+            None => 1.0,
+        };
+        let time = Duration::from_secs_f32(time);
+        let level = self.settings.level;
+        self.start_search(SearchPurpose::Move, move |engine, board| engine.play(board, level, time));
     }
-    (usize::MAX, usize::MAX)
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum Turn {
-    White,
-    Black,
-}
 
-struct Game {
-    turn: Turn,
-    board: Board,
-    selected: bool,
-    selected_x: usize,
-    selected_y: usize,
-    legal_moves: Vec<(usize, usize)>,
-    game_condition:GameCondition
-}
-
-impl Game {
-    fn new(spritesheet: Texture2D) -> Self {
-        Game {
-            turn: Turn::White,
-            board: Board::new(spritesheet),
-            selected: false,
-            selected_x: 0,
-            selected_y: 0,
-            legal_moves: Vec::new(),
-            game_condition:GameCondition::StartScreen
+    // a short full strength search for the side to move, shown as an arrow
+    fn hint_button(&mut self) {
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let waiting = self.thinking.is_some() || self.is_engine_turn();
+        if !root_ui().button(vec2(x, 70.0), if waiting { "Thinking..." } else { "Hint" }) || waiting {
+            return;
         }
+        self.metadata.hints[self.turn as usize] += 1;
+        let limits = Limits {
+            time: Some(Duration::from_millis(500)),
+            ..Limits::default()
+        };
+        self.start_search(SearchPurpose::Hint(self.board.hash()), move |engine, board| {
+            engine.search(board, limits, |_| {})
+        });
     }
 
-    fn deselect_and_clear_legal_moves(&mut self) {
-        self.selected = false;
-        self.legal_moves.clear();
+    // back to the start screen with the game kept as it is, clocks stopped
+    fn menu_button(&mut self) {
+        let x = self.board.width as f32 * BLOCKSIZE + 120.0;
+        if root_ui().button(vec2(x, 70.0), "Menu") {
+            // the computer thinks again when the game is resumed
+            self.cancel_search();
+            self.deselect_and_clear_legal_moves();
+            self.move_input.clear();
+            self.paused = true;
+            self.game_condition = GameCondition::StartScreen;
+        }
     }
-    fn change_selected_and_fetch_legal_moves(&mut self, x: usize, y: usize) {
-        self.selected=true;
-        self.selected_x = x;
-        self.selected_y = y;
 
-        self.legal_moves = self.board.get_legal_moves_for_piece(x, y);
+    fn draw_hint(&self) {
+        if let Some(mv) = self.hint {
+            draw_arrow(
+                self.screen_square(square_x(mv.from), square_y(mv.from)),
+                self.screen_square(square_x(mv.to), square_y(mv.to)),
+                Color::new(0.1, 0.7, 0.2, 0.7),
+            );
+        }
     }
-    fn move_piece(&mut self, x: usize, y: usize) {
-        let mut selected_piece = self.board.pieces[self.selected_y][self.selected_x];
-        // TODO: checking for promotion
-        if selected_piece == W && y == 0 {
-            selected_piece = WQ;
-        } else if selected_piece == B && y == self.board.height - 1 {
-            selected_piece = BQ;
+
+    fn draw_search_info(&self) {
+        let Some(info) = &self.search_info else {
+            return;
+        };
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let y = self.board.height as f32 * BLOCKSIZE / 2.0 + 70.0;
+        let pv: Vec<String> = info.pv.iter().take(4).map(|&mv| notation::to_coordinates(&self.board, mv)).collect();
+        let lines = [
+            format!("depth {}  {}", info.depth, engine::format_score(info.score)),
+            format!("{}k nodes", info.nodes / 1000),
+            format!("{}k nodes/s", info.nps / 1000),
+            pv.join(" "),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, x, y + i as f32 * 20.0, 18.0, GRAY);
         }
+    }
 
-        if selected_piece == WK{
-            if x == self.board.width -2 && y == self.board.height-1{
-                self.board.pieces[y][x-1]=WR;
-                self.board.pieces[y][x]=WK;
-                self.board.pieces[y][x+1]=E;
-            }
-            if x == 2 && y == self.board.height-1{
-                self.board.pieces[y][x+1]=WR;
-                self.board.pieces[y][x]=WK;
-                self.board.pieces[y][0]=E;
+    // what a click (or Enter on the cursor) does on the board square x, y
+    fn select_square(&mut self, x: usize, y: usize) {
+        if self.is_engine_turn() || self.is_remote_turn() {
+            return;
+        }
+        if self.selected_drop.is_some() {
+            if self.legal_moves.contains(&(x, y)) {
+                self.drop_selected(x, y);
             }
+            self.deselect_and_clear_legal_moves();
+            return;
         }
-        if selected_piece == BK{
-            if x == self.board.width -2 && y == 0{
-                self.board.pieces[y][x-1]=BR;
-                self.board.pieces[y][x]=BK;
-                self.board.pieces[y][x+1]=E;
+        // analysis has no turns, any piece can be picked up; a legal target is never picked
+        // up though, whether it is being captured or is the rook being castled with
+        let is_target = self.selected && self.legal_moves.contains(&(x, y));
+        let is_own_piece = if matches!(self.game_condition, GameCondition::Analysis) {
+            self.board.color_at(x, y).is_some() && !is_target
+        } else {
+            self.board.color_at(x, y) == Some(self.turn) && !is_target
+        };
+        if !self.selected {
+            if is_own_piece {
+                self.change_selected_and_fetch_legal_moves(x, y);
             }
-            if x == 2 && y == 0{
-                self.board.pieces[y][x+1]=BR;
-                self.board.pieces[y][x]=BK;
-                self.board.pieces[y][0]=E;
+        } else if x == self.selected_x && y == self.selected_y {
+            self.deselect_and_clear_legal_moves();
+        } else if is_own_piece {
+            self.change_selected_and_fetch_legal_moves(x, y);
+        } else {
+            if self.legal_moves.contains(&(x, y)) {
+                self.play_selected_move(x, y, None);
             }
+            self.deselect_and_clear_legal_moves();
         }
-
-        match selected_piece{
-            WK=>{
-                self.board.is_white_king_moved=true;
-                self.board.white_king_x = x;
-                self.board.white_king_y = y;
-            },
-            BK=>{
-                self.board.is_black_king_moved=true;
-                self.board.black_king_x = x;
-                self.board.black_king_y = y;
-            },
-            WR=>{
-                if self.selected_x == 0{
-                    self.board.is_left_white_rook_moved = true;
-                }
-                if self.selected_x == self.board.width -1{
-                    self.board.is_right_white_rook_moved = true;
-                }
-            },
-            BR=>{
-                if self.selected_x == 0{
-                    self.board.is_left_black_rook_moved = true;
-                }
-                if self.selected_x == self.board.width -1{
-                    self.board.is_right_black_rook_moved = true;
-                }
-            },
-            _=>{},
-        }
-        self.board.pieces[y][x] = selected_piece;
-        self.board.pieces[self.selected_y][self.selected_x] = E;
     }
 
-    fn highlight_legal_moves(&self) {
-        for &(x, y) in &self.legal_moves {
-            draw_rectangle(x as f32 * BLOCKSIZE, y as f32*BLOCKSIZE, BLOCKSIZE, BLOCKSIZE, Color::new(0.0, 0.0, 0.0, 0.2));
+    // a move worked out by the engine or typed in, played as if it had been clicked
+    fn play_move(&mut self, mv: Move) {
+        if mv.drop.is_some() {
+            self.play_drop(mv);
+        } else {
+            self.change_selected_and_fetch_legal_moves(square_x(mv.from), square_y(mv.from));
+            self.play_selected_move(square_x(mv.to), square_y(mv.to), mv.promotion);
         }
+        self.deselect_and_clear_legal_moves();
     }
-    fn run(&mut self) {
 
-        let has_legal_moves = match self.turn{
-            Turn::White=>self.board.does_white_have_legal_moves(),
-            Turn::Black=>self.board.does_black_have_legal_moves()
+    fn play_selected_move(&mut self, x: usize, y: usize, promotion: Option<Role>) {
+        let piece = self.board.piece_at(self.selected_x, self.selected_y).expect("a piece is selected");
+        let from = square(self.selected_x, self.selected_y);
+        let capture = self.board.is_capture(Move::new(from, square(x, y), None));
+        let mv = self.move_piece(x, y, promotion);
+        // a castling king slides to where it lands, not onto the rook
+        let to = if piece.role == Role::King && self.board.piece_on(mv.to) != Some(piece) {
+            self.board.castling_squares(mv).0
+        } else {
+            mv.to
         };
+        if capture && self.board.variant().explodes() {
+            self.explosion = Some(Explosion { center: (x, y), elapsed: 0.0 });
+        }
+        self.animation = Some(Animation {
+            piece,
+            from: (self.selected_x, self.selected_y),
+            to: (square_x(to), square_y(to)),
+            elapsed: 0.0,
+        });
+        self.hint = None;
+        if let Some(clock) = &mut self.clock {
+            clock.press(self.turn);
+        }
+        self.send_move(mv);
+        self.turn = self.board.side_to_move();
+    }
 
-        if !has_legal_moves {
-            match self.turn{
-                Turn::White=>{
-                    if self.board.is_white_king_in_check(self.board.white_king_x,self.board.white_king_y){
-                        self.game_condition = GameCondition::BlackWin;
-                    }
-                    else{
-                        self.game_condition = GameCondition::Draw;
-                    }
-                },
-                Turn::Black=>{
-                    if self.board.is_black_king_in_check(self.board.black_king_x,self.board.black_king_y){
-                        self.game_condition = GameCondition::WhiteWin;
-                    }
-                    else{
-                        self.game_condition = GameCondition::Draw;
-                    }
-                },
-            } 
+    // typed moves go to the text box, arrow keys move the square cursor and
+    // Enter either plays the typed move or acts like a click under the cursor
+    fn handle_keyboard(&mut self) {
+        if self.handle_chat_keyboard() {
+            return;
+        }
+        while let Some(c) = get_char_pressed() {
+            if c.is_ascii_alphanumeric() || "-=+#@".contains(c) {
+                self.move_input.push(c);
+                self.input_error = None;
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.move_input.pop();
+            self.input_error = None;
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.move_input.clear();
+            self.deselect_and_clear_legal_moves();
         }
 
-        self.board.draw();
+        let arrows = [
+            (KeyCode::Left, -1, 0),
+            (KeyCode::Right, 1, 0),
+            (KeyCode::Up, 0, -1),
+            (KeyCode::Down, 0, 1),
+        ];
+        for (key, dx, dy) in arrows {
+            if is_key_pressed(key) {
+                // the cursor moves in screen directions, so it follows a flipped board
+                let (x, y) = match self.cursor {
+                    Some((x, y)) => self.screen_square(x, y),
+                    None => self.screen_square(4, self.board.height - 1),
+                };
+                let x = (x as i32 + dx).clamp(0, self.board.width as i32 - 1) as usize;
+                let y = (y as i32 + dy).clamp(0, self.board.height as i32 - 1) as usize;
+                self.cursor = Some(self.screen_square(x, y));
+            }
+        }
 
-        if self.selected {
-            draw_rectangle(
-                self.selected_x as f32 * BLOCKSIZE - BUFF,
-                self.selected_y as f32 * BLOCKSIZE - BUFF,
-                BLOCKSIZE + 2.0 * BUFF,
-                BLOCKSIZE + 2.0 * BUFF,
-                Color::new(0.0, 0.0, 0.0, 0.2),
-            );
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+            if !self.move_input.is_empty() {
+                self.submit_move_input();
+            } else if let Some((x, y)) = self.cursor {
+                self.select_square(x, y);
+            }
         }
+    }
 
-        self.highlight_legal_moves();
+    fn submit_move_input(&mut self) {
+        if self.is_engine_turn() {
+            self.input_error = Some("wait for the computer's move".to_string());
+            return;
+        }
+        if self.is_remote_turn() {
+            self.input_error = Some("wait for your opponent's move".to_string());
+            return;
+        }
+        match notation::parse_move(&mut self.board, self.turn, &self.move_input) {
+            Ok(mv) => {
+                self.play_move(mv);
+                self.move_input.clear();
+            }
+            Err(error) => self.input_error = Some(error),
+        }
+    }
 
-        let (x, y) = detect_mouse();
-        if x != usize::MAX && y != usize::MAX && x < self.board.width && y < self.board.height {
-            if !self.selected {
-                if self.turn==Turn::White  && self.board.is_white_piece(x,y){
-                    self.change_selected_and_fetch_legal_moves(x, y);
-                }
-                if self.turn==Turn::Black  && self.board.is_black_piece(x,y){
-                    self.change_selected_and_fetch_legal_moves(x, y);
-                }
+    fn draw_cursor(&self) {
+        if let Some((x, y)) = self.cursor {
+            let (x, y) = self.screen_square(x, y);
+            draw_rectangle_lines(
+                x as f32 * BLOCKSIZE + BUFF,
+                y as f32 * BLOCKSIZE + BUFF,
+                BLOCKSIZE - 2.0 * BUFF,
+                BLOCKSIZE - 2.0 * BUFF,
+                2.0 * BUFF,
+                YELLOW,
+            );
+        }
+    }
 
-            } else if x == self.selected_x && y == self.selected_y {
-                self.deselect_and_clear_legal_moves();
-            } else if (self.board.is_black_piece(x, y)
-            && self.board.is_black_piece(self.selected_x, self.selected_y) &&
-            self.turn == Turn::Black) ||
-            (self.board.is_white_piece(x, y)
-            && self.board.is_white_piece(self.selected_x, self.selected_y)&&
-            self.turn == Turn::White){
-                self.change_selected_and_fetch_legal_moves(x, y);
-            } else {
-                if self.legal_moves.contains(&(x, y)) {
-                    self.move_piece(x, y);
-                    self.turn = match self.turn{
-                        Turn::White=>Turn::Black,
-                        Turn::Black=>Turn::White
-                    };
-                }
-                self.deselect_and_clear_legal_moves();
-            }
+    fn draw_move_input(&self) {
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let y = self.board.height as f32 * BLOCKSIZE / 2.0;
+        draw_rectangle_lines(x, y - 25.0, 160.0, 35.0, 2.0, GRAY);
+        if self.move_input.is_empty() {
+            draw_text("type a move", x + 8.0, y, 24.0, DARKGRAY);
+        } else {
+            draw_text(&self.move_input, x + 8.0, y, 24.0, WHITE);
+        }
+        if let Some(error) = &self.input_error {
+            draw_text(error, x, y + 30.0, 18.0, RED);
+        }
+    }
+    // a custom sheet that failed to load is replaced by the bundled one, say why
+    fn draw_asset_error(&self) {
+        if let Some(error) = &self.asset_error {
+            draw_text(
+                &format!("could not load piece set, using default ({})", error),
+                10.0,
+                screen_height() - 10.0,
+                20.0,
+                RED,
+            );
         }
     }
     fn screen(&mut self){
-        let win_size = vec2(400., 200.);
+        let win_size = vec2(400., 360.);
         let win_pos = vec2(
             (screen_width() - win_size.x) / 2.0,
             (screen_height() - win_size.y) / 2.0,
@@ -1104,10 +865,11 @@ impl Game {
         widgets::Window::new(hash!("screen"), win_pos, win_size)
             .label("Welcome to the Chess")
             .titlebar(false)
-            .ui(&mut *root_ui(), |ui| {
+            .ui(&mut root_ui(), |ui| {
                 let center = |y| Vec2::new((win_size.x - 200.) / 2.0, y);
 
                 let message = match self.game_condition{
+                    GameCondition::StartScreen if self.paused=>"Game paused",
                     GameCondition::StartScreen=>"welcome to chess",
                     GameCondition::Draw=>"Draw",
                     GameCondition::BlackWin=>"Black Won",
@@ -1115,9 +877,24 @@ impl Game {
                     _=>"Error"
                 };
 
-                ui.label(center(40.), message);
+                let message = match &self.metadata.end_reason {
+                    Some(reason) if !matches!(self.game_condition, GameCondition::StartScreen) => {
+                        format!("{} ({})", message, reason)
+                    }
+                    _ => message.to_string(),
+                };
+                ui.label(center(40.), &message);
+                if let Some(index) = self.metadata.chess960 {
+                    ui.label(center(78.), &format!("Chess960 position {}", index));
+                }
+                let hints = self.metadata.hints;
+                if hints != [0, 0] && !matches!(self.game_condition, GameCondition::StartScreen) {
+                    let hints_text = format!("Hints used: White {}, Black {}", hints[0], hints[1]);
+                    ui.label(center(60.), &hints_text);
+                }
                 ui.separator();
                 let first_button_text = match self.game_condition{
+                    GameCondition::StartScreen if self.paused=>"Resume game",
                     GameCondition::StartScreen=>"Start Game",
                     GameCondition::Draw=>"Back to Start Screen",
                     GameCondition::BlackWin=>"Back to Start Screen",
@@ -1128,6 +905,11 @@ impl Game {
                 if ui.button(center(100.), first_button_text) {
                     match self.game_condition{
                         GameCondition::StartScreen=>{
+                            // a paused game keeps the time it had left
+                            if !self.paused {
+                                self.clock = Clock::new(self.settings.time_control);
+                            }
+                            self.paused = false;
                             self.game_condition=GameCondition::Running
                         },
                         _=>{
@@ -1136,7 +918,147 @@ impl Game {
                     };
                 }
                 if ui.button(center(120.0),"quit"){
-                    self.game_condition = GameCondition::Quit;
+                    self.quit();
+                }
+                let analysis_text = match self.game_condition {
+                    GameCondition::StartScreen => "Analysis board",
+                    _ => "Analyse this game",
+                };
+                if ui.button(center(140.0), analysis_text) {
+                    self.save_unfinished();
+                    self.clock = None;
+                    self.game_condition = GameCondition::Analysis;
+                }
+                if !matches!(self.game_condition, GameCondition::StartScreen)
+                    && !self.moves.is_empty()
+                    && ui.button(center(160.0), "Review game")
+                {
+                    self.start_review();
+                }
+                if !matches!(self.game_condition, GameCondition::StartScreen)
+                    && ui.button(center(180.0), "Save PGN")
+                {
+                    self.file_status = Some(match self.export_pgn() {
+                        Ok(path) => format!("saved to {}", path.display()),
+                        Err(error) => format!("could not save: {}", error),
+                    });
+                }
+                if let Some(status) = &self.file_status {
+                    let y = if matches!(self.game_condition, GameCondition::StartScreen) { 340.0 } else { 200.0 };
+                    ui.label(vec2(10.0, y), status);
+                }
+                if let GameCondition::StartScreen = self.game_condition {
+                    if ui.button(center(200.0), "Settings") {
+                        self.game_condition = GameCondition::Settings;
+                    }
+                    if ui.button(center(220.0), "Set up position") {
+                        self.save_unfinished();
+                        self.start_setup();
+                    }
+                    let chess960_text = format!("Chess960: {}", if self.settings.chess960 { "On" } else { "Off" });
+                    if ui.button(center(240.0), chess960_text.as_str()) {
+                        self.save_unfinished();
+                        self.settings.chess960 = !self.settings.chess960;
+                        let _ = self.settings.save();
+                        // a fresh game picks the start position again
+                        self.game_condition = GameCondition::Restart;
+                    }
+                    let variant_text = format!("Variant: {}", self.settings.variant().name());
+                    if ui.button(center(260.0), variant_text.as_str()) {
+                        self.save_unfinished();
+                        self.settings.variant = variant::next(self.settings.variant()).name().to_string();
+                        let _ = self.settings.save();
+                        self.game_condition = GameCondition::Restart;
+                    }
+                    if ui.button(center(280.0), "Network game") {
+                        self.save_unfinished();
+                        self.open_lobby();
+                    }
+                    if self.paused && ui.button(center(300.0), "Save game") {
+                        self.file_status = Some(match self.save_game() {
+                            Ok(path) => format!("saved to {}", path.display()),
+                            Err(error) => format!("could not save: {}", error),
+                        });
+                    }
+                    if files::has_saved_game() && ui.button(center(320.0), "Load game") {
+                        self.file_status = match self.load_game() {
+                            Ok(()) => None,
+                            Err(error) => Some(format!("could not load: {}", error)),
+                        };
+                    }
+                    let opponent_text = format!("Opponent: {}", self.settings.opponent.name());
+                    if ui.button(center(160.0), opponent_text.as_str()) {
+                        self.settings.opponent = self.settings.opponent.next();
+                        let _ = self.settings.save();
+                    }
+                    let level_text = format!("Computer level: {}", self.settings.level.name());
+                    if ui.button(center(180.0), level_text.as_str()) {
+                        self.settings.level = self.settings.level.next();
+                        let _ = self.settings.save();
+                    }
+                }
+            });
+    }
+    fn settings_screen(&mut self) {
+        let win_size = vec2(400., 300.);
+        let win_pos = vec2(
+            (screen_width() - win_size.x) / 2.0,
+            (screen_height() - win_size.y) / 2.0,
+        );
+        let on_off = |value: bool| if value { "On" } else { "Off" };
+
+        widgets::Window::new(hash!("settings"), win_pos, win_size)
+            .label("Settings")
+            .titlebar(false)
+            .ui(&mut root_ui(), |ui| {
+                let center = |y| Vec2::new((win_size.x - 200.) / 2.0, y);
+                let mut changed = false;
+
+                ui.label(center(20.), "Settings");
+                ui.separator();
+                let theme_text = format!("Theme: {}", self.settings.theme.name());
+                if ui.button(center(60.0), theme_text.as_str()) {
+                    self.cycle_theme();
+                }
+                let piece_set_name = self
+                    .piece_sets
+                    .override_name()
+                    .or(self.settings.piece_set.clone())
+                    .unwrap_or("Default".to_string());
+                let piece_set_text = format!("Pieces: {}", piece_set_name);
+                if ui.button(center(80.0), piece_set_text.as_str()) {
+                    self.cycle_piece_set();
+                }
+                let clock_text = format!("Clock: {}", self.settings.time_control.name());
+                if ui.button(center(100.0), clock_text.as_str()) {
+                    self.settings.time_control = self.settings.time_control.next();
+                    changed = true;
+                }
+                let animation_text = format!("Animation: {}", self.settings.animation_speed.name());
+                if ui.button(center(120.0), animation_text.as_str()) {
+                    self.settings.animation_speed = self.settings.animation_speed.next();
+                    changed = true;
+                }
+                let hints_text = format!("Legal move hints: {}", on_off(self.settings.show_legal_moves));
+                if ui.button(center(140.0), hints_text.as_str()) {
+                    self.settings.show_legal_moves = !self.settings.show_legal_moves;
+                    changed = true;
+                }
+                let flip_text = format!("Auto-flip board: {}", on_off(self.settings.auto_flip));
+                if ui.button(center(160.0), flip_text.as_str()) {
+                    self.settings.auto_flip = !self.settings.auto_flip;
+                    changed = true;
+                }
+                let level_text = format!("Computer level: {}", self.settings.level.name());
+                if ui.button(center(180.0), level_text.as_str()) {
+                    self.settings.level = self.settings.level.next();
+                    changed = true;
+                }
+                if changed {
+                    let _ = self.settings.save();
+                }
+                if ui.button(center(200.0), "Back") {
+                    self.game_condition = GameCondition::StartScreen;
                 }
             });
     }
@@ -1144,14 +1066,31 @@ impl Game {
 
 #[macroquad::main("Chess")]
 async fn main() {
-    let sprite_sheet = load_texture("./../assets/Chess_Pieces_Sprite.png")
-        .await
-        .unwrap();
-    let mut game = Game::new(sprite_sheet.clone());
+    // `--pieces <path>` swaps the bundled sprite sheet for another one with the same layout
+    let mut args = std::env::args().skip(1);
+    let mut override_path = None;
+    while let Some(arg) = args.next() {
+        if arg == "--pieces" {
+            override_path = args.next().map(std::path::PathBuf::from);
+        }
+    }
+    let piece_sets = PieceSets::new(override_path);
+    let mut game = Game::new(piece_sets.clone(), Settings::load());
+    // closing the window goes through `quit`, so an unfinished game is saved first
+    prevent_quit();
     loop {
+        if is_quit_requested() {
+            game.quit();
+        }
         clear_background(BLACK);
         match game.game_condition{
             GameCondition::StartScreen=> game.screen(),
+            GameCondition::Settings=>game.settings_screen(),
+            GameCondition::Analysis=>game.analysis_screen(),
+            GameCondition::Review=>game.review_screen(),
+            GameCondition::Setup=>game.setup_screen(),
+            GameCondition::Lobby=>game.lobby_screen(),
+            GameCondition::Rooms=>game.rooms_screen(),
             GameCondition::Running=>game.run(),
             GameCondition::Draw =>{
                 game.screen();
@@ -1166,9 +1105,10 @@ async fn main() {
                 break;
             }
             GameCondition::Restart=>{
-                game = Game::new(sprite_sheet.clone());
+                game = Game::new(piece_sets.clone(), game.settings.clone());
             }
         }
+        game.draw_asset_error();
         next_frame().await
     }
 }
@@ -0,0 +1,254 @@
+use crate::board::{Board, Move};
+use crate::clock::TimeControl;
+use crate::notation;
+use crate::piece::Color;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+// bumped whenever a message changes shape, the two ends must agree on it
+pub const PROTOCOL_VERSION: u32 = 4;
+pub const DEFAULT_PORT: u16 = 7878;
+// how long a player who dropped out has to come back before the game is theirs to lose
+pub const ABANDON_SECONDS: f32 = 60.0;
+// the pause between attempts to reach the other end again
+const REDIAL_DELAY: Duration = Duration::from_secs(2);
+// messages waiting to go out before the other end is taken to have stopped reading
+const SEND_QUEUE: usize = 256;
+// the longest message taken from the other end, a whole game's history fits many times over
+const MAX_LINE: usize = 64 * 1024;
+// how long a single write may wait on the other end
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// one JSON object a line, moves travel in coordinate notation (`e2e4`, `e7e8q`, `N@f3`)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    // the first thing each end sends
+    Hello { version: u32 },
+    // from the host once both have said hello, `color` is the side the receiver plays and
+    // None for someone watching, who is then sent the moves played so far
+    Start {
+        variant: String,
+        fen: String,
+        time_control: TimeControl,
+        color: Option<Color>,
+    },
+    Move { mv: String },
+    Resign,
+    DrawOffer,
+    DrawAccept,
+    DrawDecline,
+    // seconds left on both clocks, sent by whoever has just moved
+    Clock { white: f32, black: f32 },
+    // `from` is filled in by a server passing the message on, None there is someone watching
+    Chat {
+        text: String,
+        #[serde(default)]
+        from: Option<Color>,
+    },
+    // given to each player once a game starts, it gets them back into it after losing
+    // the connection
+    Seat { token: String },
+    // after `Hello` on a new connection, instead of starting or joining a game
+    Resume { token: String },
+    // everything needed to carry on with a game, `clock` is White's and Black's seconds
+    Sync {
+        variant: String,
+        fen: String,
+        time_control: TimeControl,
+        color: Option<Color>,
+        moves: Vec<String>,
+        clock: Option<[f32; 2]>,
+    },
+    // `color` has dropped out and loses once `seconds` have gone by without them
+    Disconnected { color: Color, seconds: f32 },
+    Reconnected { color: Color },
+    // the rest only pass between a server and its clients
+    ListRooms,
+    Rooms { rooms: Vec<RoomInfo> },
+    // whoever creates a room plays White once somebody joins it
+    CreateRoom { name: String, variant: String, time_control: TimeControl },
+    JoinRoom { name: String },
+    WatchRoom { name: String },
+    // the server's word on how a game finished, `winner` is None for a draw
+    End { winner: Option<Color>, reason: String },
+    Error { text: String },
+}
+
+// a room as listed to clients looking for a game
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoomInfo {
+    pub name: String,
+    pub variant: String,
+    pub time_control: TimeControl,
+    pub players: usize,
+    pub spectators: usize,
+}
+
+// a line of messages to the other player, read and written on threads of their own so
+// neither polling nor sending ever blocks
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<io::Result<Message>>,
+    outgoing: SyncSender<String>,
+}
+
+impl Connection {
+    // `address` is `host:port`, the port may be left off
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let address = if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:{}", address, DEFAULT_PORT)
+        };
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or(io::Error::new(ErrorKind::NotFound, "no such address"))?;
+        Self::new(TcpStream::connect_timeout(&address, Duration::from_secs(5))?)
+    }
+
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (sender, incoming) = mpsc::channel();
+        // a line that runs past the limit without ending ends the connection instead
+        thread::spawn(move || {
+            loop {
+                let mut line = String::new();
+                let message = match (&mut reader).take(MAX_LINE as u64 + 1).read_line(&mut line) {
+                    Ok(0) => break,
+                    Ok(_) if !line.ends_with('\n') => Err(io::Error::new(ErrorKind::InvalidData, "message too long or cut off")),
+                    Ok(_) => serde_json::from_str(&line).map_err(io::Error::other),
+                    Err(error) => Err(error),
+                };
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        // a write that fails closes the socket, so the reader sees the end too
+        let mut writer = stream.try_clone()?;
+        let (outgoing, lines) = mpsc::sync_channel::<String>(SEND_QUEUE);
+        thread::spawn(move || {
+            for line in lines {
+                if writer.write_all(line.as_bytes()).is_err() {
+                    let _ = writer.shutdown(Shutdown::Both);
+                    break;
+                }
+            }
+        });
+        Ok(Connection { stream, incoming, outgoing })
+    }
+
+    // queued for the writing thread; an end that lets the queue fill up is cut off
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let mut line = serde_json::to_string(message).map_err(io::Error::other)?;
+        line.push('\n');
+        match self.outgoing.try_send(line) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let _ = self.stream.shutdown(Shutdown::Both);
+                Err(io::Error::new(ErrorKind::TimedOut, "the other end stopped reading"))
+            }
+            Err(TrySendError::Disconnected(_)) => Err(io::Error::new(ErrorKind::BrokenPipe, "connection closed")),
+        }
+    }
+
+    // the next message if one has arrived, an error once the other end has gone
+    pub fn receive(&self) -> io::Result<Option<Message>> {
+        match self.incoming.try_recv() {
+            Ok(message) => message.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(ErrorKind::ConnectionAborted, "connection closed")),
+        }
+    }
+}
+
+// the reading thread holds its own handle to the socket, so stop it; the socket closes
+// once the writing thread has sent what was still queued
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Read);
+    }
+}
+
+// waits for someone to join without holding up the caller
+pub struct Host {
+    listener: TcpListener,
+}
+
+impl Host {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Host { listener })
+    }
+
+    pub fn port(&self) -> io::Result<u16> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    // `None` until somebody has connected
+    pub fn accept(&self) -> io::Result<Option<Connection>> {
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                Connection::new(stream).map(Some)
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+// keeps dialling `address` in the background until it answers; the attempts stop once
+// the receiver is dropped
+pub fn redial(address: String) -> Receiver<io::Result<Connection>> {
+    let (sender, connections) = mpsc::channel();
+    thread::spawn(move || {
+        loop {
+            let connection = Connection::connect(&address);
+            let connected = connection.is_ok();
+            if sender.send(connection).is_err() || connected {
+                break;
+            }
+            thread::sleep(REDIAL_DELAY);
+        }
+    });
+    connections
+}
+
+// hard enough to guess on a LAN, it only has to tell two players apart
+pub fn new_token() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    let state = std::collections::hash_map::RandomState::new();
+    format!("{:016x}{:016x}", state.build_hasher().finish(), state.hash_one(std::process::id()))
+}
+
+// a move sent by the other end, which is only trusted once our own rules agree with it
+pub fn read_move(board: &mut Board, color: Color, text: &str) -> Result<Move, String> {
+    if board.side_to_move() != color {
+        return Err(format!("{} moved out of turn", color.name()));
+    }
+    let mv = notation::parse_move(board, color, text)?;
+    if board.legal_moves(color).contains(&mv) {
+        Ok(mv)
+    } else {
+        Err(format!("{} is not legal here", text))
+    }
+}
+
+pub fn check_version(version: u32) -> Result<(), String> {
+    if version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(format!("the other side speaks version {}, this is version {}", version, PROTOCOL_VERSION))
+    }
+}
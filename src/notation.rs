@@ -0,0 +1,493 @@
+use crate::bitboard::{MAX_HEIGHT, MAX_WIDTH, Square, pawn_attacks, square, square_x, square_y};
+use crate::board::{self, Board, Move};
+use crate::piece::{Color, Piece, Role};
+use crate::variant::Variant;
+
+// whatever the variant allows pawns to become
+fn promotion_role(board: &Board, letter: char) -> Option<Role> {
+    Role::from_letter(letter).filter(|role| board.variant().promotions().contains(role))
+}
+
+// files run a.. from the left, rank 1 is the bottom row (y == height - 1)
+fn file_index(file: char) -> Option<usize> {
+    file.is_ascii_lowercase().then(|| file as usize - 'a' as usize)
+}
+
+fn rank_index(board: &Board, rank: char) -> Option<usize> {
+    let rank = rank.to_digit(10)? as usize;
+    (1..=board.height).contains(&rank).then(|| board.height - rank)
+}
+
+fn parse_square(board: &Board, text: &str) -> Option<Square> {
+    let mut chars = text.chars();
+    let x = file_index(chars.next()?)?;
+    let y = rank_index(board, chars.next()?)?;
+    (chars.next().is_none() && x < board.width).then_some(square(x, y))
+}
+
+// a promotion left unnamed is a queen, like when clicking
+fn matches_promotion(mv: &Move, promotion: Option<Role>) -> bool {
+    match (mv.promotion, promotion) {
+        (None, _) => true,
+        (Some(role), None) => role == Role::Queen,
+        (Some(role), Some(wanted)) => role == wanted,
+    }
+}
+
+// accepts coordinate moves (`e2e4`, `e7e8q`), SAN (`Nf3`, `exd5`, `e8=N`, `O-O`) and
+// drops (`N@f3`, `@e4` for a pawn)
+pub fn parse_move(board: &mut Board, color: Color, text: &str) -> Result<Move, String> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+    let moves = board.legal_moves(color);
+
+    if let Some((role, square)) = text.split_once('@') {
+        let role = match role.chars().next() {
+            Some(letter) => Role::from_letter(letter.to_ascii_uppercase()).ok_or(format!("can't drop {}", letter))?,
+            None => Role::Pawn,
+        };
+        let to = parse_square(board, square).ok_or(format!("can't read {}", text))?;
+        let drop = Move::drop(role, to);
+        return if moves.contains(&drop) {
+            Ok(drop)
+        } else {
+            Err(format!("{} is not legal here", text))
+        };
+    }
+
+    let king_side = match text {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(king_side) = king_side {
+        return moves
+            .into_iter()
+            .find(|&mv| board.is_castling(mv) && (square_x(mv.to) > square_x(mv.from)) == king_side)
+            .ok_or(format!("{} is not legal here", text));
+    }
+
+    if text.is_ascii()
+        && (text.len() == 4 || text.len() == 5)
+        && let (Some(from), Some(to)) = (parse_square(board, &text[0..2]), parse_square(board, &text[2..4]))
+    {
+        let promotion = match text[4..].chars().next() {
+            Some(letter) => Some(promotion_role(board, letter).ok_or(format!("can't promote to {}", letter))?),
+            None => None,
+        };
+        // castling may also be given as the king's own move, e1g1
+        if let Some(castle) = board.castling_to(from, to) {
+            return Ok(castle);
+        }
+        return moves
+            .into_iter()
+            .find(|mv| mv.from == from && mv.to == to && matches_promotion(mv, promotion))
+            .ok_or(format!("{} is not legal here", text));
+    }
+
+    // SAN: [piece][disambiguation][x]square[=promotion]
+    let mut rest: String = text.chars().filter(|&c| c != 'x' && c != ':').collect();
+    let mut promotion = None;
+    if let Some(index) = rest.find('=') {
+        let letter = rest[index + 1..].chars().next().ok_or("missing promotion piece")?;
+        promotion = Some(promotion_role(board, letter).ok_or(format!("can't promote to {}", letter))?);
+        rest.truncate(index);
+    } else if rest.len() > 2 && rest.ends_with(|c: char| c.is_ascii_uppercase()) {
+        let letter = rest.pop().unwrap_or('Q');
+        promotion = promotion_role(board, letter);
+    }
+    // files are lower case, so any capital starting the move names the piece
+    let role = match rest.chars().next().filter(char::is_ascii_uppercase) {
+        Some(letter) => {
+            rest.remove(0);
+            Some(Role::from_letter(letter).filter(|&role| role != Role::Pawn).ok_or(format!("no piece is called {}", letter))?)
+        }
+        None => Some(Role::Pawn),
+    };
+    if rest.len() < 2 || !rest.is_ascii() {
+        return Err(format!("can't read {}", text));
+    }
+    let (disambiguation, square) = rest.split_at(rest.len() - 2);
+    let to = parse_square(board, square).ok_or(format!("can't read {}", text))?;
+    let mut from_file = None;
+    let mut from_rank = None;
+    for c in disambiguation.chars() {
+        if let Some(x) = file_index(c) {
+            from_file = Some(x);
+        } else if let Some(y) = rank_index(board, c) {
+            from_rank = Some(y);
+        } else {
+            return Err(format!("can't read {}", text));
+        }
+    }
+
+    let candidates: Vec<_> = moves
+        .into_iter()
+        .filter(|mv| {
+            mv.to == to
+                && matches_promotion(mv, promotion)
+                && board.piece_at(square_x(mv.from), square_y(mv.from)).map(|piece| piece.role) == role
+                && from_file.is_none_or(|x| x == square_x(mv.from))
+                && from_rank.is_none_or(|y| y == square_y(mv.from))
+        })
+        .collect();
+    match candidates.as_slice() {
+        [mv] => Ok(*mv),
+        [] => Err(format!("{} is not legal here", text)),
+        _ => Err(format!("{} is ambiguous", text)),
+    }
+}
+
+fn square_name(board: &Board, sq: Square) -> String {
+    format!("{}{}", (b'a' + square_x(sq) as u8) as char, board.height - square_y(sq))
+}
+
+// `mv` as `e2e4`, `e7e8q` or `N@f3`, with the ranks numbered for `board`'s height
+pub fn to_coordinates(board: &Board, mv: Move) -> String {
+    if let Some(role) = mv.drop {
+        return format!("{}@{}", role.letter(), square_name(board, mv.to));
+    }
+    let promotion = mv.promotion.map(|role| role.letter().to_ascii_lowercase());
+    let promotion = promotion.map_or(String::new(), String::from);
+    format!("{}{}{}", square_name(board, mv.from), square_name(board, mv.to), promotion)
+}
+
+// `mv` in SAN for the position on `board`, which must be legal there
+pub fn to_san(board: &mut Board, mv: Move) -> String {
+    let piece = match mv.drop {
+        Some(role) => Piece::new(board.side_to_move(), role),
+        None => match board.piece_on(mv.from) {
+            Some(piece) => piece,
+            None => return to_coordinates(board, mv),
+        },
+    };
+    let mut san = String::new();
+    if let Some(role) = mv.drop {
+        san.push(role.letter());
+        san.push('@');
+        san.push_str(&square_name(board, mv.to));
+    } else if board.is_castling(mv) {
+        san.push_str(if square_x(mv.to) > square_x(mv.from) { "O-O" } else { "O-O-O" });
+    } else {
+        let capture = board.is_capture(mv);
+        if piece.role == Role::Pawn {
+            if capture {
+                san.push((b'a' + square_x(mv.from) as u8) as char);
+            }
+        } else {
+            san.push(piece.role.letter());
+            // name the file if that tells the pieces apart, else the rank, else both
+            let others: Vec<Square> = board
+                .legal_moves(piece.color)
+                .into_iter()
+                .filter(|other| other.to == mv.to && other.from != mv.from && board.piece_on(other.from) == Some(piece))
+                .map(|other| other.from)
+                .collect();
+            if !others.is_empty() {
+                let from = square_name(board, mv.from);
+                if others.iter().all(|&other| square_x(other) != square_x(mv.from)) {
+                    san.push_str(&from[..1]);
+                } else if others.iter().all(|&other| square_y(other) != square_y(mv.from)) {
+                    san.push_str(&from[1..]);
+                } else {
+                    san.push_str(&from);
+                }
+            }
+        }
+        if capture {
+            san.push('x');
+        }
+        san.push_str(&square_name(board, mv.to));
+        if let Some(role) = mv.promotion {
+            san.push('=');
+            san.push(role.letter());
+        }
+    }
+
+    let undo = board.make_move(mv);
+    let enemy = piece.color.opposite();
+    if board.is_king_in_check(enemy) {
+        san.push(if board.has_legal_moves(enemy) { '+' } else { '#' });
+    }
+    board.unmake_move(mv, undo);
+    san
+}
+
+// a line of moves from the position on `board`, played out on a copy
+pub fn line_to_san(board: &Board, moves: &[Move]) -> String {
+    let mut board = board.clone();
+    let mut sans = Vec::with_capacity(moves.len());
+    for &mv in moves {
+        sans.push(to_san(&mut board, mv));
+        board.make_move(mv);
+    }
+    sans.join(" ")
+}
+
+// Forsyth-Edwards Notation with X-FEN castling: KQkq name the outermost rooks, any other
+// rook is named by its file like in Shredder-FEN. There is no fifty-move counter, so the
+// halfmove clock is always 0. Three-check adds the checks each side has given as +W+B
+pub fn to_fen(board: &Board, fullmove: u32) -> String {
+    let mut ranks = Vec::with_capacity(board.height);
+    for y in 0..board.height {
+        let mut rank = String::new();
+        let mut empty = 0;
+        for x in 0..board.width {
+            match board.piece_at(x, y) {
+                Some(piece) => {
+                    if empty > 0 {
+                        rank.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    rank.push(piece.fen_char());
+                    if board.is_promoted(square(x, y)) {
+                        rank.push('~');
+                    }
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            rank.push_str(&empty.to_string());
+        }
+        ranks.push(rank);
+    }
+    // pieces in hand go in brackets after the board, white's first
+    let mut placement = ranks.join("/");
+    if board.variant().drops() {
+        placement.push('[');
+        for color in [Color::White, Color::Black] {
+            let pocket = board.pocket(color);
+            let fairy = Role::ALL.into_iter().skip(Role::Pawn as usize + 1);
+            for role in [Role::Queen, Role::Rook, Role::Bishop, Role::Knight, Role::Pawn].into_iter().chain(fairy) {
+                for _ in 0..pocket[role as usize] {
+                    placement.push(Piece::new(color, role).fen_char());
+                }
+            }
+        }
+        placement.push(']');
+    }
+
+    let mut castling = String::new();
+    for (i, rook) in board.castling_rooks().iter().enumerate() {
+        let Some(x) = *rook else {
+            continue;
+        };
+        let (color, king_side) = board::castling_side(i);
+        let letter = if board.outermost_rook(color, king_side) == Some(x) {
+            if king_side { 'k' } else { 'q' }
+        } else {
+            (b'a' + x as u8) as char
+        };
+        castling.push(if color == Color::White { letter.to_ascii_uppercase() } else { letter });
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    let side = if board.side_to_move() == Color::White { "w" } else { "b" };
+    let en_passant = board.en_passant().map_or("-".to_string(), |sq| square_name(board, sq));
+    let mut fen = format!("{} {} {} {} 0 {}", placement, side, castling, en_passant, fullmove);
+    if board.variant().counts_checks() {
+        let [white, black] = board.checks();
+        fen.push_str(&format!(" +{}+{}", white, black));
+    }
+    fen
+}
+
+// Three-check's counters, either as checks given (+1+0) or as checks still to give (2+3)
+fn parse_checks(field: &str) -> Option<[u8; 2]> {
+    let (given, field) = match field.strip_prefix('+') {
+        Some(field) => (true, field),
+        None => (false, field),
+    };
+    let (white, black) = field.split_once('+')?;
+    let counts = [white.parse::<u8>().ok()?, black.parse::<u8>().ok()?];
+    if counts.iter().any(|&count| count > 3) {
+        return None;
+    }
+    Some(if given { counts } else { counts.map(|left| 3 - left) })
+}
+
+// one rank of a FEN's placement as its squares, with whether each piece was promoted
+fn parse_rank(rank: &str) -> Result<Vec<Option<(Piece, bool)>>, String> {
+    let mut squares = Vec::new();
+    let mut digits = String::new();
+    for c in rank.chars().chain(['/']) {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        if !digits.is_empty() {
+            let empty = digits.parse::<usize>().map_err(|error| error.to_string())?;
+            squares.extend((0..empty).map(|_| None));
+            digits.clear();
+        }
+        match c {
+            '/' => break,
+            '~' => {
+                if let Some(Some((_, promoted))) = squares.last_mut() {
+                    *promoted = true;
+                }
+            }
+            _ => squares.push(Some((Piece::from_fen_char(c).ok_or(format!("unknown piece {}", c))?, false))),
+        }
+    }
+    Ok(squares)
+}
+
+// reads a FEN for `variant`, taking castling as KQkq, X-FEN or Shredder-FEN and pieces in
+// hand in brackets with `~` after promoted pieces and Three-check's counters wherever they
+// come after en passant; the move counters may be left off.
+// The board is as big as the placement, from 5x5 up to 10x8
+pub fn parse_fen(text: &str, variant: &'static dyn Variant) -> Result<Board, String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let [placement, side, castling, en_passant, ref counters @ ..] = fields[..] else {
+        return Err("a FEN needs the pieces, side to move, castling and en passant".to_string());
+    };
+    let (placement, pocket) = match placement.split_once('[') {
+        Some((placement, pocket)) => (placement, pocket.strip_suffix(']').ok_or("the pocket needs a closing ]")?),
+        None => (placement, ""),
+    };
+    let ranks = placement.split('/').map(parse_rank).collect::<Result<Vec<_>, _>>()?;
+    let (width, height) = (ranks[0].len(), ranks.len());
+    if !(5..=MAX_WIDTH).contains(&width) || !(5..=MAX_HEIGHT).contains(&height) {
+        return Err(format!("boards go from 5x5 up to {}x{}, not {}x{}", MAX_WIDTH, MAX_HEIGHT, width, height));
+    }
+    let mut board = Board::with_size(width, height);
+    board.set_variant(variant);
+    for (y, rank) in ranks.iter().enumerate() {
+        if rank.len() != width {
+            return Err(format!("rank {} has {} files, not {}", height - y, rank.len(), width));
+        }
+        for (x, &contents) in rank.iter().enumerate() {
+            if let Some((piece, promoted)) = contents {
+                board.set_piece(square(x, y), Some(piece));
+                board.set_promoted(square(x, y), promoted);
+            }
+        }
+    }
+    for c in pocket.chars() {
+        let piece = Piece::from_fen_char(c).ok_or(format!("unknown piece {} in the pocket", c))?;
+        let held = board.pocket(piece.color)[piece.role as usize];
+        board.set_pocket_count(piece.color, piece.role, held + 1);
+    }
+
+    let side = match side {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return Err(format!("{} is not a side to move", side)),
+    };
+    board.set_side_to_move(side);
+
+    let mut rooks = [None; 4];
+    for c in castling.chars().filter(|&c| c != '-') {
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+        let offset = if color == Color::White { 0 } else { 2 };
+        let (king_side, rook) = match c.to_ascii_lowercase() {
+            'k' => (true, board.outermost_rook(color, true)),
+            'q' => (false, board.outermost_rook(color, false)),
+            file => {
+                let x = file_index(file).filter(|&x| x < board.width).ok_or(format!("can't read castling {}", c))?;
+                let king_x = square_x(board.pieces(color, Role::King).trailing_zeros() as Square);
+                (x > king_x, Some(x))
+            }
+        };
+        let index = offset + if king_side { 0 } else { 1 };
+        rooks[index] = Some(rook.ok_or(format!("no rook to castle with for {}", c))?);
+    }
+    if variant.castling() {
+        board.set_castling_rooks(rooks);
+    }
+
+    // like after a move, the square is only kept while a pawn could take on it
+    if en_passant != "-" {
+        let sq = parse_square(&board, en_passant).ok_or(format!("can't read en passant {}", en_passant))?;
+        if pawn_attacks(side.opposite(), sq) & board.pieces(side, Role::Pawn) != 0 {
+            board.set_en_passant(Some(sq));
+        }
+    }
+
+    if variant.counts_checks() {
+        for field in counters.iter().filter(|field| field.contains('+')) {
+            let checks = parse_checks(field).ok_or(format!("can't read the checks given {}", field))?;
+            board.set_checks(Color::White, checks[0]);
+            board.set_checks(Color::Black, checks[1]);
+        }
+    }
+
+    board.validate().map_err(|problems| problems.join(", "))?;
+    Ok(board)
+}
+
+// the move number the `index`th move of a game from `start` is written with, a game that
+// starts with Black to move has Black's move as the first of move 1
+pub fn move_number(start: &Board, index: usize) -> usize {
+    let offset = if start.side_to_move() == Color::Black { 1 } else { 0 };
+    (index + offset) / 2 + 1
+}
+
+// the game from `start` as PGN, with the tags a reader needs to replay it: the variant
+// when it isn't standard chess and the start position when it isn't the usual one, then
+// any `tags` of the caller's; each comment goes after the number of moves it is paired with
+pub fn to_pgn(
+    start: &Board,
+    moves: &[Move],
+    result: &str,
+    tags: &[(&str, String)],
+    comments: &[(usize, String)],
+) -> String {
+    let mut pgn = String::new();
+    let variant = start.variant();
+    if variant.name() != "Standard" {
+        pgn.push_str(&format!("[Variant \"{}\"]\n", variant.name()));
+    }
+    if start.hash() != Board::start_position(variant).hash() {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{}\"]\n", to_fen(start, 1)));
+    }
+    for (name, value) in tags {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+    pgn.push_str(&format!("[Result \"{}\"]\n\n", result));
+
+    let mut board = start.clone();
+    let mut words = Vec::with_capacity(moves.len() * 3 / 2 + 1);
+    // braces can't be nested in a comment, so the text's own become parentheses
+    let comments_after = |ply: usize| {
+        comments
+            .iter()
+            .filter(move |(after, _)| *after == ply)
+            .map(|(_, text)| format!("{{{}}}", text.replace('{', "(").replace('}', ")")))
+    };
+    for (i, &mv) in moves.iter().enumerate() {
+        let number = move_number(start, i);
+        let count = words.len();
+        words.extend(comments_after(i));
+        let commented = words.len() > count;
+        if board.side_to_move() == Color::White {
+            words.push(format!("{}.", number));
+        } else if i == 0 || commented {
+            words.push(format!("{}...", number));
+        }
+        words.push(to_san(&mut board, mv));
+        board.make_move(mv);
+    }
+    words.extend(comments_after(moves.len()));
+    words.push(result.to_string());
+
+    // movetext lines are kept under 80 characters
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.len() + 1 + word.len() > 79 {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
@@ -0,0 +1,575 @@
+use crate::chat::{CHAT_WIDTH, Chat};
+use crate::{BLOCKSIZE, Game, GameCondition, PANEL_WIDTH, WINDOW_HEIGHT};
+use chess::board::Move;
+use chess::clock::{Clock, TimeControl};
+use chess::net::{self, Connection, Host, Message};
+use chess::notation;
+use chess::piece::Color as Side;
+use chess::variant;
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets};
+use std::io;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+// how long someone dialling in to a hosted game has to show the token before the line is
+// freed for the next caller
+const RESUME_WAIT: Duration = Duration::from_secs(5);
+
+// hosting or joining, up until the game starts
+pub struct Lobby {
+    port: String,
+    address: String,
+    host: Option<Host>,
+    // connected, but the two ends haven't agreed on a game yet
+    peer: Option<Connection>,
+    hosting: bool,
+    status: String,
+}
+
+impl Default for Lobby {
+    fn default() -> Self {
+        Lobby {
+            port: net::DEFAULT_PORT.to_string(),
+            address: "127.0.0.1".to_string(),
+            host: None,
+            peer: None,
+            hosting: false,
+            status: String::new(),
+        }
+    }
+}
+
+// how a dropped connection is picked up again
+pub enum Reconnect {
+    // the host keeps listening for the other player to come back
+    Listen(Host),
+    Dial(String),
+}
+
+// a game against someone on another computer, or one being watched on a server
+pub struct Online {
+    connection: Connection,
+    // the side played at this end, None when watching; the other side only moves when
+    // the other end says so
+    pub color: Option<Side>,
+    // through a server, which keeps the real clocks and has the last word on results
+    pub server: bool,
+    // a draw offered by the other player that hasn't been answered
+    draw_offered: bool,
+    draw_sent: bool,
+    pub chat: Chat,
+    // the connection has dropped, until it is picked up again
+    closed: bool,
+    reconnect: Reconnect,
+    // gets a player back into the game, handed out by the host or server
+    token: Option<String>,
+    // attempts to get through again while the connection is down
+    redialing: Option<Receiver<io::Result<Connection>>>,
+    // someone dialling in to a hosted game and since when, they take over once they show
+    // the token
+    returning: Option<(Connection, Instant)>,
+    // a player who has dropped out and the seconds they have left to come back
+    away: Option<(Side, f32)>,
+}
+
+impl Online {
+    fn new(connection: Connection, color: Option<Side>, server: bool, reconnect: Reconnect) -> Self {
+        Online {
+            connection,
+            color,
+            server,
+            draw_offered: false,
+            draw_sent: false,
+            chat: Chat::default(),
+            closed: false,
+            reconnect,
+            token: None,
+            redialing: None,
+            returning: None,
+            away: None,
+        }
+    }
+
+    // a failed send shows up as a closed connection the next time it is polled
+    pub fn send(&mut self, message: Message) {
+        let _ = self.connection.send(&message);
+    }
+}
+
+impl Game {
+    pub fn open_lobby(&mut self) {
+        self.lobby = Some(Lobby::default());
+        self.game_condition = GameCondition::Lobby;
+    }
+
+    // also while dialling back in, as the other end would never hear of a move made meanwhile
+    pub fn is_remote_turn(&self) -> bool {
+        self.online.as_ref().is_some_and(|online| {
+            online.color != Some(self.turn) || (online.closed && matches!(online.reconnect, Reconnect::Dial(_)))
+        })
+    }
+
+    pub fn lobby_screen(&mut self) {
+        let Some(mut lobby) = self.lobby.take() else {
+            return;
+        };
+        let win_size = vec2(400., 300.);
+        let win_pos = vec2(
+            (screen_width() - win_size.x) / 2.0,
+            (screen_height() - win_size.y) / 2.0,
+        );
+        let mut back = false;
+        let mut server = false;
+        widgets::Window::new(hash!("lobby"), win_pos, win_size)
+            .label("Network game")
+            .titlebar(false)
+            .ui(&mut root_ui(), |ui| {
+                let center = |y| Vec2::new((win_size.x - 200.) / 2.0, y);
+                ui.label(center(20.), "Network game");
+                ui.separator();
+                widgets::InputText::new(hash!())
+                    .label("Port")
+                    .position(center(60.))
+                    .size(vec2(200., 20.))
+                    .ui(ui, &mut lobby.port);
+                if ui.button(center(85.), "Host") {
+                    lobby.host_on_port();
+                }
+                widgets::InputText::new(hash!())
+                    .label("Address")
+                    .position(center(130.))
+                    .size(vec2(200., 20.))
+                    .ui(ui, &mut lobby.address);
+                if ui.button(center(155.), "Join") {
+                    lobby.join();
+                }
+                if ui.button(center(180.), "Connect to server") {
+                    server = true;
+                }
+                ui.label(center(210.), &lobby.status);
+                back = ui.button(center(250.), "Back");
+            });
+        if back {
+            self.game_condition = GameCondition::StartScreen;
+            return;
+        }
+        if server {
+            self.open_rooms();
+            return;
+        }
+        if let Err(error) = self.poll_lobby(&mut lobby) {
+            lobby.status = error;
+            lobby.peer = None;
+        }
+        if matches!(self.game_condition, GameCondition::Lobby) {
+            self.lobby = Some(lobby);
+        }
+    }
+
+    // both ends say hello with their protocol version, then the host says what is played
+    fn poll_lobby(&mut self, lobby: &mut Lobby) -> Result<(), String> {
+        // the host keeps listening during the game, for the other player coming back
+        if let Some(host) = lobby.host.as_ref().filter(|_| lobby.peer.is_none())
+            && let Some(mut connection) = host.accept().map_err(|error| error.to_string())?
+        {
+            connection.send(&Message::Hello { version: net::PROTOCOL_VERSION }).map_err(|error| error.to_string())?;
+            lobby.peer = Some(connection);
+            lobby.hosting = true;
+        }
+        let Some(peer) = &mut lobby.peer else {
+            return Ok(());
+        };
+        while let Some(message) = peer.receive().map_err(|error| error.to_string())? {
+            match message {
+                Message::Hello { version } => {
+                    net::check_version(version)?;
+                    if lobby.hosting {
+                        let start = Message::Start {
+                            variant: self.start.variant().name().to_string(),
+                            fen: notation::to_fen(&self.start, 1),
+                            time_control: self.settings.time_control,
+                            color: Some(Side::Black),
+                        };
+                        let token = net::new_token();
+                        peer.send(&start).map_err(|error| error.to_string())?;
+                        peer.send(&Message::Seat { token: token.clone() }).map_err(|error| error.to_string())?;
+                        // whatever was on the board before, the game starts from what the peer was sent
+                        self.play_from(self.start.clone(), false);
+                        let host = lobby.host.take().expect("hosting");
+                        let peer = lobby.peer.take().expect("connected");
+                        self.begin_online(peer, Some(Side::White), false, Reconnect::Listen(host));
+                        if let Some(online) = &mut self.online {
+                            online.token = Some(token);
+                        }
+                        return Ok(());
+                    }
+                }
+                Message::Start { variant, fen, time_control, color } => {
+                    self.set_up_online(&variant, &fen, time_control)?;
+                    let address = Reconnect::Dial(lobby.address.trim().to_string());
+                    self.begin_online(lobby.peer.take().expect("connected"), color, false, address);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // the position and clock the host or server has chosen
+    pub fn set_up_online(&mut self, variant: &str, fen: &str, time_control: TimeControl) -> Result<(), String> {
+        let variant = variant::from_name(variant).ok_or(format!("unknown variant {}", variant))?;
+        let board = notation::parse_fen(fen, variant)?;
+        self.play_from(board, false);
+        self.clock = Clock::new(time_control);
+        Ok(())
+    }
+
+    pub fn begin_online(&mut self, connection: Connection, color: Option<Side>, server: bool, reconnect: Reconnect) {
+        self.cancel_search();
+        self.online = Some(Online::new(connection, color, server, reconnect));
+        self.game_condition = GameCondition::Running;
+        let width = self.board.width as f32 * BLOCKSIZE + PANEL_WIDTH + CHAT_WIDTH;
+        request_new_screen_size(width, WINDOW_HEIGHT);
+    }
+
+    // puts the game back together from what the host or server remembers of it
+    pub fn resume_online(
+        &mut self,
+        variant: &str,
+        fen: &str,
+        time_control: TimeControl,
+        moves: &[String],
+        clock: Option<[f32; 2]>,
+    ) -> Result<(), String> {
+        self.set_up_online(variant, fen, time_control)?;
+        self.replay(moves)?;
+        if let (Some(clock), Some([white, black])) = (&mut self.clock, clock) {
+            clock.set(Side::White, white);
+            clock.set(Side::Black, black);
+        }
+        Ok(())
+    }
+
+    // every move played so far in coordinate notation, the way they went over the network
+    pub fn history(&self) -> Vec<String> {
+        self.moves.iter().map(|&(mv, _)| notation::to_coordinates(&self.start, mv)).collect()
+    }
+
+    // what the host sends a player coming back, `color` being theirs
+    fn sync_message(&self, color: Option<Side>) -> Message {
+        Message::Sync {
+            variant: self.start.variant().name().to_string(),
+            fen: notation::to_fen(&self.start, 1),
+            time_control: self.settings.time_control,
+            color,
+            moves: self.history(),
+            clock: self.clock.as_ref().map(|clock| [clock.remaining(Side::White), clock.remaining(Side::Black)]),
+        }
+    }
+
+    fn finish_online(&mut self, winner: Option<Side>, reason: &str) {
+        self.game_condition = match winner {
+            Some(Side::White) => GameCondition::WhiteWin,
+            Some(Side::Black) => GameCondition::BlackWin,
+            None => GameCondition::Draw,
+        };
+        self.metadata.end_reason = Some(reason.to_string());
+    }
+
+    // everything the other end has sent since the last frame
+    pub fn poll_online(&mut self) {
+        self.count_down_away();
+        self.reconnect_online();
+        let Some(online) = self.online.as_mut().filter(|online| !online.closed) else {
+            return;
+        };
+        let mut messages = Vec::new();
+        let closed = loop {
+            match online.connection.receive() {
+                Ok(Some(message)) => messages.push(message),
+                Ok(None) => break false,
+                Err(_) => break true,
+            }
+        };
+        for message in messages {
+            self.handle_message(message);
+        }
+        if closed {
+            self.connection_lost();
+        }
+    }
+
+    // a host gives the other player a while to come back, anyone else starts dialling back
+    // in; someone watching has no seat to get back to
+    fn connection_lost(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        online.closed = true;
+        match &online.reconnect {
+            Reconnect::Listen(_) => online.away = online.color.map(|local| (local.opposite(), net::ABANDON_SECONDS)),
+            Reconnect::Dial(address) if online.token.is_some() => online.redialing = Some(net::redial(address.clone())),
+            Reconnect::Dial(_) => online.chat.note("the connection was lost"),
+        }
+    }
+
+    // the other player may dial back in before the host has noticed they dropped, so a host
+    // listens all game long rather than only once the connection is down
+    fn reconnect_online(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        if let Reconnect::Listen(host) = &online.reconnect {
+            if online.returning.is_none()
+                && let Ok(Some(mut connection)) = host.accept()
+                && connection.send(&Message::Hello { version: net::PROTOCOL_VERSION }).is_ok()
+            {
+                online.returning = Some((connection, Instant::now()));
+            }
+            self.check_returning();
+            return;
+        }
+        let Some(redialing) = online.redialing.as_ref().filter(|_| online.closed) else {
+            return;
+        };
+        let Ok(Ok(connection)) = redialing.try_recv() else {
+            return;
+        };
+        online.connection = connection;
+        online.closed = false;
+        online.redialing = None;
+        online.send(Message::Hello { version: net::PROTOCOL_VERSION });
+        if let Some(token) = online.token.clone() {
+            online.send(Message::Resume { token });
+        }
+    }
+
+    // the right token replaces whatever is left of the old connection, anything else is
+    // hung up on, and so is a caller who says nothing for too long
+    fn check_returning(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        let Some((returning, since)) = &online.returning else {
+            return;
+        };
+        let resumed = loop {
+            match returning.receive() {
+                Ok(Some(Message::Resume { token })) => break online.token.as_ref() == Some(&token),
+                Ok(Some(_)) => {}
+                Ok(None) if since.elapsed() < RESUME_WAIT => return,
+                Ok(None) | Err(_) => break false,
+            }
+        };
+        let Some((returning, _)) = online.returning.take().filter(|_| resumed) else {
+            return;
+        };
+        online.connection = returning;
+        online.closed = false;
+        online.away = None;
+        let color = online.color.map(Side::opposite);
+        let sync = self.sync_message(color);
+        if let Some(online) = &mut self.online {
+            online.send(sync);
+        }
+    }
+
+    // only the host rules on abandonment itself, a server says so with `End`
+    fn count_down_away(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        let Some((color, seconds)) = &mut online.away else {
+            return;
+        };
+        *seconds = (*seconds - get_frame_time()).max(0.0);
+        if *seconds == 0.0 && matches!(online.reconnect, Reconnect::Listen(_)) {
+            let winner = color.opposite();
+            self.finish_online(Some(winner), "abandoned");
+        }
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        match message {
+            Message::Move { mv } => {
+                let mover = match online.color {
+                    Some(local) => local.opposite(),
+                    None => self.board.side_to_move(),
+                };
+                match net::read_move(&mut self.board, mover, &mv) {
+                    Ok(mv) => {
+                        online.draw_sent = false;
+                        self.play_move(mv);
+                    }
+                    Err(error) if online.server => online.chat.note(error),
+                    Err(_) => self.finish_online(Some(mover.opposite()), "illegal move from opponent"),
+                }
+            }
+            Message::Clock { white, black } => {
+                if let Some(clock) = &mut self.clock {
+                    clock.set(Side::White, white);
+                    clock.set(Side::Black, black);
+                }
+            }
+            Message::Resign => {
+                if let Some(local) = online.color {
+                    self.finish_online(Some(local), "resignation");
+                }
+            }
+            Message::DrawOffer => online.draw_offered = true,
+            Message::DrawAccept if online.draw_sent => self.finish_online(None, "agreement"),
+            Message::DrawDecline => {
+                online.draw_sent = false;
+                online.chat.note("draw declined");
+            }
+            // without a server in between, whatever arrives is from the other player
+            Message::Chat { text, from } => {
+                let from = match from {
+                    Some(color) => color.name(),
+                    None if online.server => "Spectator",
+                    None => online.color.map_or("Opponent", |local| local.opposite().name()),
+                };
+                online.chat.hear(self.moves.len(), from, text);
+            }
+            Message::End { winner, reason } => self.finish_online(winner, &reason),
+            Message::Error { text } => online.chat.note(text),
+            // someone watching a room sees the game start again once its second player arrives
+            Message::Start { variant, fen, time_control, color: None } if online.color.is_none() => {
+                if let Err(error) = self.set_up_online(&variant, &fen, time_control)
+                    && let Some(online) = &mut self.online
+                {
+                    online.chat.note(error);
+                }
+            }
+            Message::Seat { token } => online.token = Some(token),
+            Message::Hello { version } => {
+                if net::check_version(version).is_err() {
+                    online.closed = true;
+                }
+            }
+            Message::Sync { variant, fen, time_control, moves, clock, .. } => {
+                if let Err(error) = self.resume_online(&variant, &fen, time_control, &moves, clock)
+                    && let Some(online) = &mut self.online
+                {
+                    online.chat.note(error);
+                }
+            }
+            Message::Disconnected { color, seconds } => online.away = Some((color, seconds)),
+            Message::Reconnected { .. } => online.away = None,
+            // a player coming back is dealt with on the connection they came back on
+            Message::Start { .. }
+            | Message::Resume { .. }
+            | Message::DrawAccept
+            | Message::ListRooms
+            | Message::Rooms { .. }
+            | Message::CreateRoom { .. }
+            | Message::JoinRoom { .. }
+            | Message::WatchRoom { .. } => {}
+        }
+    }
+
+    // tells the other end about a move made here, along with both clocks after it
+    pub fn send_move(&mut self, mv: Move) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        if online.color != Some(self.turn) {
+            return;
+        }
+        // moving instead of answering turns a draw offer down
+        if online.draw_offered {
+            online.draw_offered = false;
+            online.send(Message::DrawDecline);
+        }
+        online.send(Message::Move { mv: notation::to_coordinates(&self.board, mv) });
+        if let Some(clock) = &self.clock
+            && !online.server
+        {
+            online.send(Message::Clock {
+                white: clock.remaining(Side::White),
+                black: clock.remaining(Side::Black),
+            });
+        }
+    }
+
+    // resigning and draws take the place of the hint button
+    pub fn draw_online_panel(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let Some(local) = online.color else {
+            draw_text("watching", x, 85.0, 18.0, GRAY);
+            self.draw_connection();
+            return;
+        };
+        if root_ui().button(vec2(x, 70.0), "Resign") {
+            online.send(Message::Resign);
+            self.finish_online(Some(local.opposite()), "resignation");
+            return;
+        }
+        if online.draw_offered {
+            if root_ui().button(vec2(x + 70.0, 70.0), "Accept draw") {
+                online.send(Message::DrawAccept);
+                self.finish_online(None, "agreement");
+                return;
+            }
+        } else if online.draw_sent {
+            draw_text("draw offered", x + 70.0, 85.0, 18.0, GRAY);
+        } else if root_ui().button(vec2(x + 70.0, 70.0), "Offer draw") {
+            online.send(Message::DrawOffer);
+            online.draw_sent = true;
+        }
+        self.draw_connection();
+    }
+
+    fn draw_connection(&self) {
+        let Some(online) = &self.online else {
+            return;
+        };
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let y = self.board.height as f32 * BLOCKSIZE / 2.0 + 70.0;
+        if online.redialing.is_some() {
+            draw_text("reconnecting...", x, y, 18.0, ORANGE);
+        } else if let Some((color, seconds)) = online.away {
+            let text = format!("{} disconnected, {}s", color.name(), seconds.ceil());
+            draw_text(&text, x, y, 18.0, ORANGE);
+        }
+    }
+}
+
+impl Lobby {
+    fn host_on_port(&mut self) {
+        let Ok(port) = self.port.trim().parse() else {
+            self.status = format!("{} is not a port", self.port);
+            return;
+        };
+        match Host::bind(port).and_then(|host| Ok((host.port()?, host))) {
+            Ok((port, host)) => {
+                self.host = Some(host);
+                self.status = format!("waiting for someone on port {}", port);
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    fn join(&mut self) {
+        let joined = Connection::connect(self.address.trim()).and_then(|mut connection| {
+            connection.send(&Message::Hello { version: net::PROTOCOL_VERSION })?;
+            Ok(connection)
+        });
+        match joined {
+            Ok(connection) => {
+                self.peer = Some(connection);
+                self.hosting = false;
+                self.status = "waiting for the host".to_string();
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+}
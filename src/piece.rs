@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub fn opposite(self) -> Self {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Color::White => "White",
+            Color::Black => "Black",
+        }
+    }
+}
+
+// ordered like the columns of the sprite sheet, which may stop after the pawn; the fairy
+// pieces come last
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Role {
+    King,
+    Queen,
+    Bishop,
+    Knight,
+    Rook,
+    Pawn,
+    // bishop and knight
+    Archbishop,
+    // rook and knight
+    Chancellor,
+    // queen and knight
+    Amazon,
+    // leaps three squares one way and one the other
+    Camel,
+}
+
+// how a piece moves: the jumps it makes, ignoring anything in between, and the steps it
+// repeats until it hits something; each (dx, dy) stands for all its mirrored and turned
+// versions, so (2, 1) is every knight move
+pub struct Movement {
+    pub leaps: &'static [(i32, i32)],
+    pub slides: &'static [(i32, i32)],
+}
+
+impl Role {
+    pub const COUNT: usize = 10;
+    pub const ALL: [Role; Role::COUNT] = [
+        Role::King,
+        Role::Queen,
+        Role::Bishop,
+        Role::Knight,
+        Role::Rook,
+        Role::Pawn,
+        Role::Archbishop,
+        Role::Chancellor,
+        Role::Amazon,
+        Role::Camel,
+    ];
+
+    // pawns move differently from how they capture, so they have no descriptor
+    pub fn movement(self) -> Option<Movement> {
+        let movement = |leaps, slides| Some(Movement { leaps, slides });
+        match self {
+            Role::King => movement(&[(1, 0), (1, 1)], &[]),
+            Role::Queen => movement(&[], &[(1, 0), (1, 1)]),
+            Role::Bishop => movement(&[], &[(1, 1)]),
+            Role::Knight => movement(&[(2, 1)], &[]),
+            Role::Rook => movement(&[], &[(1, 0)]),
+            Role::Pawn => None,
+            Role::Archbishop => movement(&[(2, 1)], &[(1, 1)]),
+            Role::Chancellor => movement(&[(2, 1)], &[(1, 0)]),
+            Role::Amazon => movement(&[(2, 1)], &[(1, 0), (1, 1)]),
+            Role::Camel => movement(&[(3, 1)], &[]),
+        }
+    }
+
+    // the upper case letter used by SAN and by white in FEN
+    pub fn letter(self) -> char {
+        match self {
+            Role::King => 'K',
+            Role::Queen => 'Q',
+            Role::Bishop => 'B',
+            Role::Knight => 'N',
+            Role::Rook => 'R',
+            Role::Pawn => 'P',
+            Role::Archbishop => 'A',
+            Role::Chancellor => 'C',
+            Role::Amazon => 'M',
+            Role::Camel => 'L',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Self> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.letter() == letter.to_ascii_uppercase())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Piece {
+    pub color: Color,
+    pub role: Role,
+}
+
+impl Piece {
+    pub fn new(color: Color, role: Role) -> Self {
+        Piece { color, role }
+    }
+
+    // white is upper case and black lower case, as in FEN
+    pub fn fen_char(self) -> char {
+        match self.color {
+            Color::White => self.role.letter(),
+            Color::Black => self.role.letter().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn from_fen_char(c: char) -> Option<Self> {
+        let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+        Role::from_letter(c).map(|role| Piece::new(color, role))
+    }
+
+    // Unicode has no symbols for the fairy pieces, they show as their FEN letter
+    pub fn glyph(self) -> char {
+        match (self.color, self.role) {
+            (Color::White, Role::King) => '♔',
+            (Color::White, Role::Queen) => '♕',
+            (Color::White, Role::Rook) => '♖',
+            (Color::White, Role::Bishop) => '♗',
+            (Color::White, Role::Knight) => '♘',
+            (Color::White, Role::Pawn) => '♙',
+            (Color::Black, Role::King) => '♚',
+            (Color::Black, Role::Queen) => '♛',
+            (Color::Black, Role::Rook) => '♜',
+            (Color::Black, Role::Bishop) => '♝',
+            (Color::Black, Role::Knight) => '♞',
+            (Color::Black, Role::Pawn) => '♟',
+            _ => self.fen_char(),
+        }
+    }
+
+    pub fn from_glyph(glyph: char) -> Option<Self> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(|color| Role::ALL.map(|role| Piece::new(color, role)))
+            .find(|piece| piece.glyph() == glyph)
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{BLOCKSIZE, Game, draw_piece_sized};
+use chess::bitboard::{square, square_x, square_y};
+use chess::board::Move;
+use chess::piece::{Color as Side, Piece, Role};
+use macroquad::prelude::*;
+
+const POCKET_SIZE: f32 = 30.0;
+// the order pieces are laid out in a pocket, kings are never captured into one
+pub const POCKET_ROLES: [Role; 5] = [Role::Queen, Role::Rook, Role::Bishop, Role::Knight, Role::Pawn];
+
+impl Game {
+    // each side's pocket sits on its own half of the side panel, next to its clock
+    fn pocket_y(&self, color: Side) -> f32 {
+        let top = if self.is_flipped() { Side::White } else { Side::Black };
+        if color == top {
+            100.0
+        } else {
+            self.board.height as f32 * BLOCKSIZE - 100.0
+        }
+    }
+
+    fn pocket_role(&self, (mouse_x, mouse_y): (f32, f32)) -> Option<(Side, Role)> {
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let column = ((mouse_x - x) / POCKET_SIZE).floor();
+        if !(0.0..POCKET_ROLES.len() as f32).contains(&column) {
+            return None;
+        }
+        [Side::White, Side::Black]
+            .into_iter()
+            .find(|&color| (self.pocket_y(color)..self.pocket_y(color) + POCKET_SIZE).contains(&mouse_y))
+            .map(|color| (color, POCKET_ROLES[column as usize]))
+    }
+
+    // empty slots are dimmed so the layout doesn't shift as pieces come and go
+    pub fn draw_pockets(&self) {
+        if !self.board.variant().drops() {
+            return;
+        }
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        for color in [Side::White, Side::Black] {
+            let y = self.pocket_y(color);
+            let pocket = self.board.pocket(color);
+            for (column, &role) in POCKET_ROLES.iter().enumerate() {
+                let piece_x = x + column as f32 * POCKET_SIZE;
+                let count = pocket[role as usize];
+                if self.selected_drop == Some(role) && color == self.turn {
+                    draw_rectangle(piece_x, y, POCKET_SIZE, POCKET_SIZE, Color::new(1.0, 1.0, 1.0, 0.3));
+                }
+                draw_piece_sized(&self.spritesheet, Piece::new(color, role), piece_x, y, POCKET_SIZE);
+                if count == 0 {
+                    draw_rectangle(piece_x, y, POCKET_SIZE, POCKET_SIZE, Color::new(0.0, 0.0, 0.0, 0.6));
+                } else {
+                    draw_text(&count.to_string(), piece_x + POCKET_SIZE - 8.0, y + POCKET_SIZE + 4.0, 16.0, WHITE);
+                }
+            }
+        }
+        // a piece being dragged out of the pocket follows the mouse
+        if let Some(role) = self.selected_drop
+            && is_mouse_button_down(MouseButton::Left)
+        {
+            let (mouse_x, mouse_y) = mouse_position();
+            let half = BLOCKSIZE / 2.0;
+            draw_piece_sized(&self.spritesheet, Piece::new(self.turn, role), mouse_x - half, mouse_y - half, BLOCKSIZE);
+        }
+    }
+
+    // a piece is picked from the pocket with a click, then dropped by clicking a square
+    // or by letting go of it over one
+    pub fn handle_pockets(&mut self) {
+        if !self.board.variant().drops() || self.is_engine_turn() || self.is_remote_turn() {
+            return;
+        }
+        if is_mouse_button_pressed(MouseButton::Left)
+            && let Some((color, role)) = self.pocket_role(mouse_position())
+            && color == self.turn
+            && self.board.pocket(color)[role as usize] > 0
+        {
+            self.select_drop(role);
+        }
+        if is_mouse_button_released(MouseButton::Left)
+            && self.selected_drop.is_some()
+            && let Some((x, y)) = self.mouse_board_square()
+        {
+            if self.legal_moves.contains(&(x, y)) {
+                self.drop_selected(x, y);
+            }
+            self.deselect_and_clear_legal_moves();
+        }
+    }
+
+    fn mouse_board_square(&self) -> Option<(usize, usize)> {
+        let (mouse_x, mouse_y) = mouse_position();
+        let (x, y) = ((mouse_x / BLOCKSIZE) as usize, (mouse_y / BLOCKSIZE) as usize);
+        let on_board = mouse_x >= 0.0 && mouse_y >= 0.0 && x < self.board.width && y < self.board.height;
+        on_board.then(|| self.screen_square(x, y))
+    }
+
+    fn select_drop(&mut self, role: Role) {
+        self.selected = false;
+        self.selected_drop = Some(role);
+        self.legal_moves = self
+            .board
+            .legal_moves(self.turn)
+            .into_iter()
+            .filter(|mv| mv.drop == Some(role))
+            .map(|mv| (square_x(mv.to), square_y(mv.to)))
+            .collect();
+    }
+
+    pub fn drop_selected(&mut self, x: usize, y: usize) {
+        if let Some(role) = self.selected_drop {
+            self.play_drop(Move::drop(role, square(x, y)));
+        }
+    }
+
+    // a dropped piece just appears, there is no square for it to slide from
+    pub fn play_drop(&mut self, mv: Move) {
+        let undo = self.board.make_move(mv);
+        self.moves.push((mv, undo));
+        self.animation = None;
+        self.hint = None;
+        if let Some(clock) = &mut self.clock {
+            clock.press(self.turn);
+        }
+        self.send_move(mv);
+        self.turn = self.board.side_to_move();
+    }
+}
@@ -0,0 +1,174 @@
+use crate::board::{Board, Move};
+use crate::engine::{Engine, Limits, game_history};
+use crate::notation;
+use crate::piece::Color;
+use std::time::Duration;
+
+// evaluations are capped here so one mate doesn't swamp the rest of the game
+pub const EVAL_CAP: i32 = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Classification {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    pub const ALL: [Classification; 5] = [
+        Classification::Best,
+        Classification::Good,
+        Classification::Inaccuracy,
+        Classification::Mistake,
+        Classification::Blunder,
+    ];
+
+    // by how many centipawns the move made the mover's position worse
+    fn from_loss(loss: i32) -> Self {
+        match loss {
+            ..50 => Classification::Good,
+            50..100 => Classification::Inaccuracy,
+            100..300 => Classification::Mistake,
+            _ => Classification::Blunder,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Classification::Best => "Best",
+            Classification::Good => "Good",
+            Classification::Inaccuracy => "Inaccuracy",
+            Classification::Mistake => "Mistake",
+            Classification::Blunder => "Blunder",
+        }
+    }
+
+    // the usual annotation symbol, empty for the unremarkable ones
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Classification::Best | Classification::Good => "",
+            Classification::Inaccuracy => "?!",
+            Classification::Mistake => "?",
+            Classification::Blunder => "??",
+        }
+    }
+}
+
+pub struct MoveReview {
+    pub mv: Move,
+    pub san: String,
+    pub color: Color,
+    // what the engine would have played instead
+    pub best: Option<Move>,
+    pub best_san: Option<String>,
+    pub loss: i32,
+    pub accuracy: f32,
+    pub classification: Classification,
+}
+
+pub struct Review {
+    pub moves: Vec<MoveReview>,
+    // white's evaluation of every position, the start included, capped in centipawns
+    pub evals: Vec<i32>,
+    // indexed by colour
+    pub accuracy: [f32; 2],
+}
+
+impl Review {
+    pub fn count(&self, color: Color, classification: Classification) -> usize {
+        self.moves
+            .iter()
+            .filter(|review| review.color == color && review.classification == classification)
+            .count()
+    }
+}
+
+// the chance of winning, in percent, for an evaluation in centipawns
+fn win_percent(eval: i32) -> f32 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.003_682_08 * eval as f32).exp()) - 1.0)
+}
+
+// the accuracy of a move from how much of the winning chance it gave away
+fn move_accuracy(before: i32, after: i32) -> f32 {
+    let drop = win_percent(before) - win_percent(after);
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+// searches every position of the game for `time` each, calling `progress` with
+// the number of positions done so far
+pub fn review_game(
+    engine: &mut Engine,
+    start: &Board,
+    moves: &[Move],
+    time: Duration,
+    mut progress: impl FnMut(usize),
+) -> Review {
+    let limits = Limits {
+        time: Some(time),
+        ..Limits::default()
+    };
+    let mut board = start.clone();
+    let mut evals = Vec::with_capacity(moves.len() + 1);
+    let mut best_moves = Vec::with_capacity(moves.len() + 1);
+    for i in 0..=moves.len() {
+        engine.set_game_history(game_history(start, &moves[..i]));
+        let info = engine.search(&mut board, limits, |_| {});
+        // a finished game's last position scores as mated or stalemated
+        let white_score = match board.side_to_move() {
+            Color::White => info.score,
+            Color::Black => -info.score,
+        };
+        evals.push(white_score.clamp(-EVAL_CAP, EVAL_CAP));
+        best_moves.push(info.best_move());
+        progress(i + 1);
+        if let Some(&mv) = moves.get(i) {
+            board.make_move(mv);
+        }
+    }
+
+    let mut board = start.clone();
+    let mut reviews = Vec::with_capacity(moves.len());
+    for (i, &mv) in moves.iter().enumerate() {
+        let color = board.side_to_move();
+        let sign = if color == Color::White { 1 } else { -1 };
+        let (before, after) = (sign * evals[i], sign * evals[i + 1]);
+        let loss = (before - after).max(0);
+        let classification = if Some(mv) == best_moves[i] {
+            Classification::Best
+        } else {
+            Classification::from_loss(loss)
+        };
+        let best_san = best_moves[i].map(|best| notation::to_san(&mut board, best));
+        reviews.push(MoveReview {
+            mv,
+            san: notation::to_san(&mut board, mv),
+            color,
+            best: best_moves[i],
+            best_san,
+            loss,
+            accuracy: move_accuracy(before, after),
+            classification,
+        });
+        board.make_move(mv);
+    }
+
+    let accuracy = [Color::White, Color::Black].map(|color| {
+        let own: Vec<f32> = reviews
+            .iter()
+            .filter(|review| review.color == color)
+            .map(|review| review.accuracy)
+            .collect();
+        if own.is_empty() {
+            100.0
+        } else {
+            own.iter().sum::<f32>() / own.len() as f32
+        }
+    });
+    Review {
+        moves: reviews,
+        evals,
+        accuracy,
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{BLOCKSIZE, Game, GameCondition, draw_arrow, draw_board};
+use chess::bitboard::{square_x, square_y};
+use chess::board::{Board, Move};
+use chess::engine::Engine;
+use chess::notation;
+use chess::piece::Color as Side;
+use chess::review::{self, Classification, Review};
+use macroquad::prelude::*;
+use macroquad::ui::root_ui;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+const TIME_PER_POSITION: Duration = Duration::from_millis(250);
+const GRAPH_HEIGHT: f32 = 120.0;
+
+enum ReviewUpdate {
+    Progress(usize),
+    Done(Review),
+}
+
+// the review runs on its own thread with its own engine, the screen steps
+// through the game while it waits
+pub struct ReviewScreen {
+    updates: Receiver<ReviewUpdate>,
+    positions: usize,
+    done: usize,
+    review: Option<Review>,
+    // how many moves into the game the shown position is
+    ply: usize,
+    shown: Board,
+}
+
+fn classification_color(classification: Classification) -> Color {
+    match classification {
+        Classification::Best => SKYBLUE,
+        Classification::Good => LIGHTGRAY,
+        Classification::Inaccuracy => YELLOW,
+        Classification::Mistake => ORANGE,
+        Classification::Blunder => RED,
+    }
+}
+
+impl Game {
+    pub fn start_review(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let start = self.start.clone();
+        let moves: Vec<Move> = self.moves.iter().map(|&(mv, _)| mv).collect();
+        let positions = moves.len() + 1;
+        thread::spawn(move || {
+            let mut engine = Engine::new();
+            let review = review::review_game(&mut engine, &start, &moves, TIME_PER_POSITION, |done| {
+                let _ = sender.send(ReviewUpdate::Progress(done));
+            });
+            let _ = sender.send(ReviewUpdate::Done(review));
+        });
+        self.review = Some(ReviewScreen {
+            updates: receiver,
+            positions,
+            done: 0,
+            review: None,
+            ply: self.moves.len(),
+            shown: self.board.clone(),
+        });
+        self.game_condition = GameCondition::Review;
+    }
+
+    fn show_ply(&mut self, ply: usize) {
+        let Some(screen) = &mut self.review else {
+            return;
+        };
+        screen.ply = ply.min(self.moves.len());
+        screen.shown = self.start.clone();
+        for &(mv, _) in &self.moves[..screen.ply] {
+            screen.shown.make_move(mv);
+        }
+    }
+
+    pub fn review_screen(&mut self) {
+        let flipped = self.is_flipped();
+        let Some(screen) = &mut self.review else {
+            self.game_condition = GameCondition::Restart;
+            return;
+        };
+        while let Ok(update) = screen.updates.try_recv() {
+            match update {
+                ReviewUpdate::Progress(done) => screen.done = done,
+                ReviewUpdate::Done(review) => screen.review = Some(review),
+            }
+        }
+        let ply = screen.ply;
+
+        draw_board(&screen.shown, &self.spritesheet, self.settings.theme, flipped);
+        self.draw_review_panel();
+        self.draw_eval_graph();
+
+        if is_key_pressed(KeyCode::Left) {
+            self.show_ply(ply.saturating_sub(1));
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.show_ply(ply + 1);
+        }
+        if is_mouse_button_pressed(MouseButton::Left)
+            && let Some(ply) = self.graph_ply(mouse_position())
+        {
+            self.show_ply(ply);
+        }
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let y = self.board.height as f32 * BLOCKSIZE - 30.0;
+        if root_ui().button(vec2(x, y), "Back") {
+            self.game_condition = GameCondition::Restart;
+        }
+    }
+
+    // the graph sits at the bottom of the side panel
+    fn graph_rect(&self) -> Rect {
+        let x = self.board.width as f32 * BLOCKSIZE + 10.0;
+        let width = screen_width() - x - 10.0;
+        let y = self.board.height as f32 * BLOCKSIZE - 50.0 - GRAPH_HEIGHT;
+        Rect::new(x, y, width, GRAPH_HEIGHT)
+    }
+
+    fn graph_ply(&self, (mouse_x, mouse_y): (f32, f32)) -> Option<usize> {
+        let screen = self.review.as_ref()?;
+        screen.review.as_ref()?;
+        let rect = self.graph_rect();
+        if !rect.contains(vec2(mouse_x, mouse_y)) {
+            return None;
+        }
+        let moves = (screen.positions - 1).max(1);
+        Some(((mouse_x - rect.x) / rect.w * moves as f32).round() as usize)
+    }
+
+    // white's evaluation over the game, white's advantage upwards
+    fn draw_eval_graph(&self) {
+        let Some(screen) = &self.review else {
+            return;
+        };
+        let Some(review) = &screen.review else {
+            return;
+        };
+        let rect = self.graph_rect();
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(0.15, 0.15, 0.15, 1.0));
+        draw_line(rect.x, rect.y + rect.h / 2.0, rect.x + rect.w, rect.y + rect.h / 2.0, 1.0, DARKGRAY);
+        let moves = (review.evals.len() - 1).max(1);
+        let point = |i: usize| {
+            let eval = review.evals[i] as f32 / review::EVAL_CAP as f32;
+            vec2(rect.x + rect.w * i as f32 / moves as f32, rect.y + rect.h / 2.0 * (1.0 - eval))
+        };
+        for i in 1..review.evals.len() {
+            let (from, to) = (point(i - 1), point(i));
+            draw_line(from.x, from.y, to.x, to.y, 2.0, WHITE);
+            let classification = review.moves[i - 1].classification;
+            if matches!(classification, Classification::Mistake | Classification::Blunder) {
+                draw_circle(to.x, to.y, 3.0, classification_color(classification));
+            }
+        }
+        let current = point(screen.ply.min(review.evals.len() - 1));
+        draw_line(current.x, rect.y, current.x, rect.y + rect.h, 1.0, YELLOW);
+    }
+
+    fn draw_review_panel(&self) {
+        let Some(screen) = &self.review else {
+            return;
+        };
+        let x = self.board.width as f32 * BLOCKSIZE + 20.0;
+        let Some(review) = &screen.review else {
+            let text = format!("reviewing {}/{}", screen.done, screen.positions);
+            draw_text(&text, x, 30.0, 20.0, GRAY);
+            return;
+        };
+
+        let mut y = 30.0;
+        for side in [Side::White, Side::Black] {
+            let text = format!("{} {:.1}%", side.name(), review.accuracy[side as usize]);
+            draw_text(&text, x, y, 20.0, WHITE);
+            y += 18.0;
+            let mut counts = String::new();
+            for classification in [Classification::Inaccuracy, Classification::Mistake, Classification::Blunder] {
+                let count = review.count(side, classification);
+                counts.push_str(&format!("{}{}  ", count, classification.symbol()));
+            }
+            draw_text(&counts, x, y, 16.0, LIGHTGRAY);
+            y += 26.0;
+        }
+
+        // the move that led to the shown position, and what the engine preferred
+        y += 10.0;
+        let Some(played) = screen.ply.checked_sub(1).map(|i| &review.moves[i]) else {
+            draw_text("start position", x, y, 18.0, GRAY);
+            return;
+        };
+        let number = notation::move_number(&self.start, screen.ply - 1);
+        let dots = if played.color == Side::White { "." } else { "..." };
+        let text = format!("{}{} {}{}", number, dots, played.san, played.classification.symbol());
+        draw_text(&text, x, y, 20.0, WHITE);
+        y += 20.0;
+        draw_text(played.classification.name(), x, y, 18.0, classification_color(played.classification));
+        if played.classification != Classification::Best
+            && let Some(best_san) = &played.best_san
+        {
+            y += 20.0;
+            draw_text(&format!("best was {}", best_san), x, y, 18.0, GRAY);
+            if let Some(best) = played.best {
+                // the board has already moved on, but the arrow still shows the idea
+                let from = self.screen_square(square_x(best.from), square_y(best.from));
+                let to = self.screen_square(square_x(best.to), square_y(best.to));
+                draw_arrow(from, to, Color::new(0.1, 0.7, 0.2, 0.5));
+            }
+        }
+    }
+}
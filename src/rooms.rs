@@ -0,0 +1,171 @@
+use crate::online::Reconnect;
+use crate::{Game, GameCondition};
+use chess::net::{self, Connection, Message, RoomInfo};
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets};
+
+// as many rooms as fit in the window, the rest wait for a room to close
+const ROOM_ROWS: usize = 5;
+
+// connected to a server and choosing a room to play or watch in
+pub struct Rooms {
+    address: String,
+    connection: Option<Connection>,
+    rooms: Vec<RoomInfo>,
+    // what a new room is called
+    name: String,
+    status: String,
+}
+
+impl Rooms {
+    fn connect(&mut self) {
+        let connected = Connection::connect(self.address.trim()).and_then(|mut connection| {
+            connection.send(&Message::Hello { version: net::PROTOCOL_VERSION })?;
+            Ok(connection)
+        });
+        match connected {
+            Ok(connection) => {
+                self.connection = Some(connection);
+                self.status = "connecting".to_string();
+            }
+            Err(error) => self.status = error.to_string(),
+        }
+    }
+
+    fn send(&mut self, message: Message) {
+        if let Some(connection) = &mut self.connection
+            && let Err(error) = connection.send(&message)
+        {
+            self.status = error.to_string();
+        }
+    }
+}
+
+impl Game {
+    pub fn open_rooms(&mut self) {
+        self.rooms = Some(Rooms {
+            address: "127.0.0.1".to_string(),
+            connection: None,
+            rooms: Vec::new(),
+            name: String::new(),
+            status: String::new(),
+        });
+        self.game_condition = GameCondition::Rooms;
+    }
+
+    pub fn rooms_screen(&mut self) {
+        let Some(mut rooms) = self.rooms.take() else {
+            return;
+        };
+        let win_size = vec2(400., 360.);
+        let win_pos = vec2(
+            (screen_width() - win_size.x) / 2.0,
+            (screen_height() - win_size.y) / 2.0,
+        );
+        let mut back = false;
+        let mut request = None;
+        widgets::Window::new(hash!("rooms"), win_pos, win_size)
+            .label("Connect to server")
+            .titlebar(false)
+            .ui(&mut root_ui(), |ui| {
+                let center = |y| Vec2::new((win_size.x - 200.) / 2.0, y);
+                ui.label(center(20.), "Connect to server");
+                ui.separator();
+                widgets::InputText::new(hash!())
+                    .label("Address")
+                    .position(center(50.))
+                    .size(vec2(200., 20.))
+                    .ui(ui, &mut rooms.address);
+                if rooms.connection.is_none() {
+                    if ui.button(center(75.), "Connect") {
+                        rooms.connect();
+                    }
+                } else {
+                    if rooms.rooms.is_empty() {
+                        ui.label(center(110.), "no rooms yet");
+                    }
+                    for (i, room) in rooms.rooms.iter().take(ROOM_ROWS).enumerate() {
+                        let y = 110. + i as f32 * 25.;
+                        let text = format!(
+                            "{} - {} {} - {}/2, {} watching",
+                            room.name,
+                            room.variant,
+                            room.time_control.name(),
+                            room.players,
+                            room.spectators
+                        );
+                        ui.label(vec2(10., y), &text);
+                        if room.players < 2 && ui.button(vec2(290., y), "Join") {
+                            request = Some(Message::JoinRoom { name: room.name.clone() });
+                        }
+                        if ui.button(vec2(335., y), "Watch") {
+                            request = Some(Message::WatchRoom { name: room.name.clone() });
+                        }
+                    }
+                    widgets::InputText::new(hash!())
+                        .label("Room")
+                        .position(center(240.))
+                        .size(vec2(200., 20.))
+                        .ui(ui, &mut rooms.name);
+                    // the new room plays the variant and clock picked on the start screen
+                    if ui.button(center(265.), "Create room") {
+                        request = Some(Message::CreateRoom {
+                            name: rooms.name.clone(),
+                            variant: self.settings.variant().name().to_string(),
+                            time_control: self.settings.time_control,
+                        });
+                        rooms.status = format!("waiting in {} for an opponent", rooms.name.trim());
+                    }
+                }
+                ui.label(center(300.), &rooms.status);
+                back = ui.button(center(325.), "Back");
+            });
+        if back {
+            self.game_condition = GameCondition::StartScreen;
+            return;
+        }
+        if let Some(message) = request {
+            rooms.send(message);
+        }
+        if let Err(error) = self.poll_rooms(&mut rooms) {
+            rooms.status = error;
+            rooms.connection = None;
+            rooms.rooms.clear();
+        }
+        if matches!(self.game_condition, GameCondition::Rooms) {
+            self.rooms = Some(rooms);
+        }
+    }
+
+    // the room list stays up to date until the server says a game has started
+    fn poll_rooms(&mut self, rooms: &mut Rooms) -> Result<(), String> {
+        let Some(connection) = &rooms.connection else {
+            return Ok(());
+        };
+        while let Some(message) = connection.receive().map_err(|error| error.to_string())? {
+            match message {
+                Message::Hello { version } => {
+                    net::check_version(version)?;
+                    rooms.status = "connected".to_string();
+                }
+                Message::Rooms { rooms: list } => rooms.rooms = list,
+                Message::Error { text } => rooms.status = text,
+                Message::Start { variant, fen, time_control, color } => {
+                    self.set_up_online(&variant, &fen, time_control)?;
+                    let address = Reconnect::Dial(rooms.address.trim().to_string());
+                    self.begin_online(rooms.connection.take().expect("connected"), color, true, address);
+                    return Ok(());
+                }
+                // watching a game that has already started
+                Message::Sync { variant, fen, time_control, color, moves, clock } => {
+                    self.resume_online(&variant, &fen, time_control, &moves, clock)?;
+                    let address = Reconnect::Dial(rooms.address.trim().to_string());
+                    self.begin_online(rooms.connection.take().expect("connected"), color, true, address);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
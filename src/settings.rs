@@ -0,0 +1,144 @@
+use chess::clock::TimeControl;
+use chess::engine::Level;
+use chess::piece::Color;
+use chess::variant::{self, Variant};
+use crate::theme::Theme;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum AnimationSpeed {
+    Off,
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl AnimationSpeed {
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimationSpeed::Off => "Off",
+            AnimationSpeed::Slow => "Slow",
+            AnimationSpeed::Normal => "Normal",
+            AnimationSpeed::Fast => "Fast",
+        }
+    }
+
+    // seconds a piece takes to slide to its new square
+    pub fn duration(self) -> f32 {
+        match self {
+            AnimationSpeed::Off => 0.0,
+            AnimationSpeed::Slow => 0.4,
+            AnimationSpeed::Normal => 0.2,
+            AnimationSpeed::Fast => 0.1,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            AnimationSpeed::Off => AnimationSpeed::Slow,
+            AnimationSpeed::Slow => AnimationSpeed::Normal,
+            AnimationSpeed::Normal => AnimationSpeed::Fast,
+            AnimationSpeed::Fast => AnimationSpeed::Off,
+        }
+    }
+}
+
+// who plays against the person at the board
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Opponent {
+    #[default]
+    Human,
+    ComputerPlaysBlack,
+    ComputerPlaysWhite,
+}
+
+impl Opponent {
+    pub fn name(self) -> &'static str {
+        match self {
+            Opponent::Human => "Human",
+            Opponent::ComputerPlaysBlack => "Computer as Black",
+            Opponent::ComputerPlaysWhite => "Computer as White",
+        }
+    }
+
+    pub fn engine_side(self) -> Option<Color> {
+        match self {
+            Opponent::Human => None,
+            Opponent::ComputerPlaysBlack => Some(Color::Black),
+            Opponent::ComputerPlaysWhite => Some(Color::White),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Opponent::Human => Opponent::ComputerPlaysBlack,
+            Opponent::ComputerPlaysBlack => Opponent::ComputerPlaysWhite,
+            Opponent::ComputerPlaysWhite => Opponent::Human,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub theme: Theme,
+    pub piece_set: Option<String>,
+    pub time_control: TimeControl,
+    pub animation_speed: AnimationSpeed,
+    pub show_legal_moves: bool,
+    pub auto_flip: bool,
+    pub opponent: Opponent,
+    pub level: Level,
+    pub chess960: bool,
+    // by name, see `chess::variant::from_name`
+    pub variant: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            theme: Theme::default(),
+            piece_set: None,
+            time_control: TimeControl::default(),
+            animation_speed: AnimationSpeed::default(),
+            show_legal_moves: true,
+            auto_flip: false,
+            opponent: Opponent::default(),
+            level: Level::default(),
+            chess960: false,
+            variant: variant::Standard.name().to_string(),
+        }
+    }
+}
+
+impl Settings {
+    // a name from an older or edited file falls back to standard chess
+    pub fn variant(&self) -> &'static dyn Variant {
+        variant::from_name(&self.variant).unwrap_or(&variant::Standard)
+    }
+
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("chess").join("settings.json"))
+    }
+
+    // a missing or unreadable file just gives the defaults
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, text)
+    }
+}
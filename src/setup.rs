@@ -0,0 +1,277 @@
+use crate::pocket::POCKET_ROLES;
+use crate::{BLOCKSIZE, Game, GameCondition, draw_board, draw_piece_sized};
+use chess::bitboard::{Square, square, square_x};
+use chess::board::{Board, Move, Undo};
+use chess::clock::Clock;
+use chess::net;
+use chess::piece::{Color as Side, Piece, Role};
+use macroquad::prelude::*;
+use macroquad::ui::root_ui;
+use std::sync::atomic::Ordering;
+
+const PALETTE_SIZE: f32 = 30.0;
+// each colour's pieces take two rows of the palette
+const PALETTE_COLUMNS: usize = 5;
+const PALETTE_ROWS: usize = Role::COUNT.div_ceil(PALETTE_COLUMNS) * 2;
+// roughly how many characters of an explanation fit across the side panel
+const WRAP: usize = 26;
+
+// the position being built, side to move and castling rights live on the board itself
+pub struct Setup {
+    board: Board,
+    // the piece following the mouse
+    held: Option<Piece>,
+    en_passant_file: Option<usize>,
+}
+
+// splits `text` on spaces into lines of at most `width` characters
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+impl Setup {
+    // the square behind the pawn that has just moved two, seen from the side to move
+    fn apply_en_passant(&mut self) {
+        let rank = match self.board.side_to_move() {
+            Side::White => 2,
+            Side::Black => self.board.height - 3,
+        };
+        self.board.set_en_passant(self.en_passant_file.map(|file| square(file, rank)));
+    }
+}
+
+// the board is left wherever the moves got to, even when one of them turns out not to be legal
+pub fn replay(board: &mut Board, moves: &[String]) -> Result<Vec<(Move, Undo)>, String> {
+    let mut played = Vec::with_capacity(moves.len());
+    for text in moves {
+        let color = board.side_to_move();
+        let mv = net::read_move(board, color, text)?;
+        played.push((mv, board.make_move(mv)));
+    }
+    Ok(played)
+}
+
+impl Game {
+    // starts from whatever is on the board, the start position when coming from the start screen
+    pub fn start_setup(&mut self) {
+        // the analysis board's search has nothing to look at while the position changes
+        self.stop.store(true, Ordering::Relaxed);
+        let board = self.board.clone();
+        let en_passant_file = board.en_passant().map(square_x);
+        self.setup = Some(Setup {
+            board,
+            held: None,
+            en_passant_file,
+        });
+        self.game_condition = GameCondition::Setup;
+    }
+
+    fn palette_piece(&self, (mouse_x, mouse_y): (f32, f32)) -> Option<Piece> {
+        let x = self.board.width as f32 * BLOCKSIZE + 10.0;
+        let column = ((mouse_x - x) / PALETTE_SIZE).floor();
+        let row = ((mouse_y - 10.0) / PALETTE_SIZE).floor();
+        if !(0.0..PALETTE_COLUMNS as f32).contains(&column) || !(0.0..PALETTE_ROWS as f32).contains(&row) {
+            return None;
+        }
+        let (row, column) = (row as usize, column as usize);
+        let color = if row < PALETTE_ROWS / 2 { Side::White } else { Side::Black };
+        let index = row % (PALETTE_ROWS / 2) * PALETTE_COLUMNS + column;
+        Role::ALL.get(index).map(|&role| Piece::new(color, role))
+    }
+
+    fn mouse_square(&self) -> Option<Square> {
+        let (mouse_x, mouse_y) = mouse_position();
+        let (x, y) = ((mouse_x / BLOCKSIZE) as usize, (mouse_y / BLOCKSIZE) as usize);
+        (mouse_x >= 0.0 && mouse_y >= 0.0 && x < self.board.width && y < self.board.height).then(|| square(x, y))
+    }
+
+    // pieces are dragged from the palette or around the board, dropping one off the board
+    // removes it and a right click empties a square
+    pub fn setup_screen(&mut self) {
+        let palette_piece = self.palette_piece(mouse_position());
+        let mouse_square = self.mouse_square();
+        let Some(setup) = &mut self.setup else {
+            self.game_condition = GameCondition::Restart;
+            return;
+        };
+        if is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(piece) = palette_piece {
+                setup.held = Some(piece);
+            } else if let Some(sq) = mouse_square
+                && let Some(piece) = setup.board.piece_on(sq)
+            {
+                setup.held = Some(piece);
+                setup.board.set_piece(sq, None);
+            }
+        }
+        if is_mouse_button_released(MouseButton::Left)
+            && let Some(piece) = setup.held.take()
+            && let Some(sq) = mouse_square
+        {
+            setup.board.set_piece(sq, Some(piece));
+        }
+        if is_mouse_button_pressed(MouseButton::Right)
+            && let Some(sq) = mouse_square
+        {
+            setup.board.set_piece(sq, None);
+        }
+        // in Crazyhouse a right click on the palette puts the piece in that side's pocket
+        if is_mouse_button_pressed(MouseButton::Right)
+            && setup.board.variant().drops()
+            && let Some(piece) = palette_piece.filter(|piece| POCKET_ROLES.contains(&piece.role))
+        {
+            let held = setup.board.pocket(piece.color)[piece.role as usize];
+            setup.board.set_pocket_count(piece.color, piece.role, held.saturating_add(1));
+        }
+
+        draw_board(&setup.board, &self.spritesheet, self.settings.theme, false);
+        let x = self.board.width as f32 * BLOCKSIZE + 10.0;
+        for (side_row, color) in [Side::White, Side::Black].into_iter().enumerate() {
+            for (index, &role) in Role::ALL.iter().enumerate() {
+                let row = side_row * PALETTE_ROWS / 2 + index / PALETTE_COLUMNS;
+                let column = index % PALETTE_COLUMNS;
+                let (piece_x, piece_y) = (x + column as f32 * PALETTE_SIZE, 10.0 + row as f32 * PALETTE_SIZE);
+                draw_piece_sized(&self.spritesheet, Piece::new(color, role), piece_x, piece_y, PALETTE_SIZE);
+            }
+        }
+        if let Some(piece) = setup.held {
+            let (mouse_x, mouse_y) = mouse_position();
+            let half = BLOCKSIZE / 2.0;
+            draw_piece_sized(&self.spritesheet, piece, mouse_x - half, mouse_y - half, BLOCKSIZE);
+        }
+        self.setup_buttons();
+    }
+
+    fn setup_buttons(&mut self) {
+        let x = self.board.width as f32 * BLOCKSIZE + 10.0;
+        let Some(setup) = &mut self.setup else {
+            return;
+        };
+        let mut y = 20.0 + PALETTE_ROWS as f32 * PALETTE_SIZE;
+        let mut next_y = || {
+            y += 22.0;
+            y
+        };
+
+        let side_text = format!("{} to move", setup.board.side_to_move().name());
+        if root_ui().button(vec2(x, next_y()), side_text.as_str()) {
+            let side = setup.board.side_to_move().opposite();
+            setup.board.set_side_to_move(side);
+            setup.apply_en_passant();
+        }
+        let names = ["White O-O", "White O-O-O", "Black O-O", "Black O-O-O"];
+        let mut rights = setup.board.castling_rights();
+        for (i, name) in names.iter().enumerate() {
+            let text = format!("{}: {}", name, if rights[i] { "yes" } else { "no" });
+            if root_ui().button(vec2(x, next_y()), text.as_str()) {
+                rights[i] = !rights[i];
+                setup.board.set_castling_rights(rights);
+            }
+        }
+        let file_name = setup.en_passant_file.map_or("-".to_string(), |file| ((b'a' + file as u8) as char).to_string());
+        let en_passant_text = format!("En passant: {}", file_name);
+        if root_ui().button(vec2(x, next_y()), en_passant_text.as_str()) {
+            setup.en_passant_file = match setup.en_passant_file {
+                None => Some(0),
+                Some(file) if file + 1 < setup.board.width => Some(file + 1),
+                Some(_) => None,
+            };
+            setup.apply_en_passant();
+        }
+        // a fresh board keeps the variant and size being set up for
+        let variant = setup.board.variant();
+        if root_ui().button(vec2(x, next_y()), "Clear") {
+            setup.board = Board::with_size(setup.board.width, setup.board.height);
+            setup.board.set_variant(variant);
+            setup.en_passant_file = None;
+        }
+        if root_ui().button(vec2(x, next_y()), "Start position") {
+            setup.board = Board::start_position(variant);
+            setup.en_passant_file = None;
+        }
+
+        if setup.board.variant().drops() {
+            for color in [Side::White, Side::Black] {
+                let pocket = setup.board.pocket(color);
+                let held: String = POCKET_ROLES
+                    .iter()
+                    .map(|&role| Piece::new(color, role).fen_char().to_string().repeat(pocket[role as usize] as usize))
+                    .collect();
+                let text = format!("{} pocket: {}", color.name(), if held.is_empty() { "-" } else { &held });
+                draw_text(&text, x, next_y() + 14.0, 16.0, WHITE);
+            }
+            if root_ui().button(vec2(x, next_y()), "Empty pockets") {
+                for color in [Side::White, Side::Black] {
+                    for role in POCKET_ROLES {
+                        setup.board.set_pocket_count(color, role, 0);
+                    }
+                }
+            }
+        }
+
+        let play = root_ui().button(vec2(x, next_y()), "Play");
+        let analyse = root_ui().button(vec2(x, next_y()), "Analyse");
+        if root_ui().button(vec2(x, next_y()), "Back") {
+            self.game_condition = GameCondition::Restart;
+            return;
+        }
+
+        let problems = setup.board.validate().err().unwrap_or_default();
+        let mut y = next_y() + 20.0;
+        for problem in &problems {
+            for line in wrap(problem, WRAP) {
+                draw_text(&line, x, y, 16.0, RED);
+                y += 16.0;
+            }
+            y += 4.0;
+        }
+        if problems.is_empty() && (play || analyse) {
+            let board = setup.board.clone();
+            self.setup = None;
+            self.play_from(board, analyse);
+        }
+    }
+
+    // a set up position becomes the start of a new game
+    pub fn play_from(&mut self, board: Board, analyse: bool) {
+        // a search still running would play its move into the new game
+        self.cancel_search();
+        self.turn = board.side_to_move();
+        self.start = board.clone();
+        self.board = board;
+        self.moves.clear();
+        self.paused = false;
+        self.metadata.chess960 = None;
+        self.hint = None;
+        self.animation = None;
+        self.explosion = None;
+        self.deselect_and_clear_legal_moves();
+        if analyse {
+            self.clock = None;
+            self.game_condition = GameCondition::Analysis;
+        } else {
+            self.clock = Clock::new(self.settings.time_control);
+            self.game_condition = GameCondition::Running;
+        }
+    }
+
+    // plays moves given in coordinate notation on from the current position
+    pub fn replay(&mut self, moves: &[String]) -> Result<(), String> {
+        let played = replay(&mut self.board, moves)?;
+        self.moves.extend(played);
+        self.turn = self.board.side_to_move();
+        Ok(())
+    }
+}
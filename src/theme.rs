@@ -0,0 +1,121 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Brown,
+    Green,
+    Blue,
+}
+
+impl Theme {
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Brown => "Classic Brown",
+            Theme::Green => "Green",
+            Theme::Blue => "Blue",
+        }
+    }
+
+    pub fn light(self) -> Color {
+        match self {
+            Theme::Brown => Color::from_rgba(240, 217, 181, 255),
+            Theme::Green => Color::from_rgba(238, 238, 210, 255),
+            Theme::Blue => SKYBLUE,
+        }
+    }
+
+    pub fn dark(self) -> Color {
+        match self {
+            Theme::Brown => Color::from_rgba(181, 136, 99, 255),
+            Theme::Green => Color::from_rgba(118, 150, 86, 255),
+            Theme::Blue => BLUE,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Theme::Brown => Theme::Green,
+            Theme::Green => Theme::Blue,
+            Theme::Blue => Theme::Brown,
+        }
+    }
+}
+
+// piece sets are png sprite sheets with the same 6x2 layout as Chess_Pieces_Sprite.png,
+// dropped into the pieces folder of the config dir
+pub fn piece_sets_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chess").join("pieces"))
+}
+
+pub fn available_piece_sets() -> Vec<String> {
+    let mut sets = Vec::new();
+    let Some(dir) = piece_sets_dir() else {
+        return sets;
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return sets;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+            && let Some(name) = path.file_name().and_then(|name| name.to_str())
+        {
+            sets.push(name.to_string());
+        }
+    }
+    sets.sort();
+    sets
+}
+
+const BUNDLED_SPRITE_SHEET: &[u8] = include_bytes!("../assets/Chess_Pieces_Sprite.png");
+
+fn load_sprite_sheet(path: &Path) -> Result<Texture2D, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let image = Image::from_file_with_format(&bytes, Some(ImageFormat::Png))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Texture2D::from_image(&image))
+}
+
+#[derive(Clone)]
+pub struct PieceSets {
+    bundled: Texture2D,
+    override_path: Option<PathBuf>,
+    pub names: Vec<String>,
+}
+
+impl PieceSets {
+    // `override_path` wins over whatever piece set is picked in the settings
+    pub fn new(override_path: Option<PathBuf>) -> Self {
+        PieceSets {
+            bundled: Texture2D::from_file_with_format(BUNDLED_SPRITE_SHEET, Some(ImageFormat::Png)),
+            override_path,
+            names: available_piece_sets(),
+        }
+    }
+
+    pub fn override_name(&self) -> Option<String> {
+        self.override_path
+            .as_ref()
+            .map(|path| path.display().to_string())
+    }
+
+    // `None` is the bundled sheet, otherwise a file name inside `piece_sets_dir`.
+    // a sheet that can't be loaded falls back to the bundled one along with the reason
+    pub fn load(&self, piece_set: &Option<String>) -> (Texture2D, Option<String>) {
+        let path = match (&self.override_path, piece_set) {
+            (Some(path), _) => path.clone(),
+            (None, Some(name)) => match piece_sets_dir() {
+                Some(dir) => dir.join(name),
+                None => return (self.bundled.clone(), Some("no config directory".to_string())),
+            },
+            (None, None) => return (self.bundled.clone(), None),
+        };
+        match load_sprite_sheet(&path) {
+            Ok(texture) => (texture, None),
+            Err(error) => (self.bundled.clone(), Some(error)),
+        }
+    }
+}
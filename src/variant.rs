@@ -0,0 +1,326 @@
+use crate::bitboard::{square_x, square_y, squares};
+use crate::board::{Board, Move};
+use crate::piece::{Color, Role};
+
+// how a game ended, `winner` is None for a draw and `reason` is shown next to the result
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GameEnd {
+    pub winner: Option<Color>,
+    pub reason: &'static str,
+}
+
+pub const STANDARD_BACK_RANK: [Role; 8] = [
+    Role::Rook,
+    Role::Knight,
+    Role::Bishop,
+    Role::Queen,
+    Role::King,
+    Role::Bishop,
+    Role::Knight,
+    Role::Rook,
+];
+
+// the rules that differ between variants; the board asks its variant while generating
+// moves, and the frontends and engine ask it whether and how the game is over
+pub trait Variant: Sync {
+    fn name(&self) -> &'static str;
+
+    // the board's height and white's back rank, whose length is the board's width;
+    // black mirrors it and each side has a rank of pawns in front
+    fn start(&self) -> (usize, &'static [Role]) {
+        (8, &STANDARD_BACK_RANK)
+    }
+
+    // a royal king may never be left in check, otherwise it is just another piece
+    fn royal_king(&self) -> bool {
+        true
+    }
+
+    fn castling(&self) -> bool {
+        true
+    }
+
+    fn promotions(&self) -> &'static [Role] {
+        &[Role::Queen, Role::Rook, Role::Bishop, Role::Knight]
+    }
+
+    // whether the board keeps count of the checks each side has given
+    fn counts_checks(&self) -> bool {
+        false
+    }
+
+    // whether captured pieces go into the capturer's pocket to be dropped back in
+    fn drops(&self) -> bool {
+        false
+    }
+
+    // whether a capture blows up the capturer and every piece but pawns around the target;
+    // kings can't capture then, so two kings side by side keep each other out of check
+    fn explodes(&self) -> bool {
+        false
+    }
+
+    // narrows the moves that follow the piece patterns down to the legal ones
+    fn filter_moves(&self, board: &mut Board, color: Color, moves: &mut Vec<Move>) {
+        moves.retain(|&mv| board.leaves_king_safe(mv, color));
+    }
+
+    // an ending that doesn't wait for the side to move to run out of moves
+    fn end(&self, _board: &Board) -> Option<GameEnd> {
+        None
+    }
+
+    // what it means for the side to move to have no legal moves
+    fn end_without_moves(&self, board: &Board) -> GameEnd {
+        let color = board.side_to_move();
+        if board.is_king_in_check(color) {
+            GameEnd { winner: Some(color.opposite()), reason: "checkmate" }
+        } else {
+            GameEnd { winner: None, reason: "stalemate" }
+        }
+    }
+
+    // the engine's material and placement score turned towards the variant's goal,
+    // from the side to move's point of view
+    fn evaluate(&self, _board: &Board, score: i32) -> i32 {
+        score
+    }
+}
+
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+}
+
+// bringing the king to one of the four centre squares wins
+pub struct KingOfTheHill;
+
+// how many king moves the king of `color` is from the hill
+fn hill_distance(board: &Board, color: Color) -> i32 {
+    let Some(king) = squares(board.pieces(color, Role::King)).next() else {
+        return 8;
+    };
+    let distance = |v: usize, size: usize| {
+        let (low, high) = (size / 2 - 1, size / 2);
+        if v < low { low - v } else { v.saturating_sub(high) }
+    };
+    distance(square_x(king), board.width).max(distance(square_y(king), board.height)) as i32
+}
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn end(&self, board: &Board) -> Option<GameEnd> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| hill_distance(board, color) == 0)
+            .map(|color| GameEnd { winner: Some(color), reason: "king of the hill" })
+    }
+
+    fn evaluate(&self, board: &Board, score: i32) -> i32 {
+        let color = board.side_to_move();
+        let closeness = |color| (4 - hill_distance(board, color)).max(0) * 40;
+        score + closeness(color) - closeness(color.opposite())
+    }
+}
+
+// giving a third check wins
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn counts_checks(&self) -> bool {
+        true
+    }
+
+    fn end(&self, board: &Board) -> Option<GameEnd> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| board.checks()[color as usize] >= 3)
+            .map(|color| GameEnd { winner: Some(color), reason: "third check" })
+    }
+
+    fn evaluate(&self, board: &Board, score: i32) -> i32 {
+        const CHECK_VALUES: [i32; 3] = [0, 150, 450];
+        let checks = board.checks();
+        let color = board.side_to_move() as usize;
+        let value = |given: u8| CHECK_VALUES[(given as usize).min(2)];
+        score + value(checks[color]) - value(checks[1 - color])
+    }
+}
+
+// captures are compulsory, the king is an ordinary piece and whoever has no moves
+// left, usually for having lost every piece, wins
+pub struct Antichess;
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "Antichess"
+    }
+
+    fn royal_king(&self) -> bool {
+        false
+    }
+
+    fn castling(&self) -> bool {
+        false
+    }
+
+    fn promotions(&self) -> &'static [Role] {
+        &[Role::Queen, Role::Rook, Role::Bishop, Role::Knight, Role::King]
+    }
+
+    fn filter_moves(&self, board: &mut Board, _color: Color, moves: &mut Vec<Move>) {
+        if moves.iter().any(|&mv| board.is_capture(mv)) {
+            moves.retain(|&mv| board.is_capture(mv));
+        }
+    }
+
+    fn end_without_moves(&self, board: &Board) -> GameEnd {
+        let color = board.side_to_move();
+        let has_pieces = Role::ALL.iter().any(|&role| board.pieces(color, role) != 0);
+        let reason = if has_pieces { "no moves left" } else { "all pieces lost" };
+        GameEnd { winner: Some(color), reason }
+    }
+
+    // losing material is the goal
+    fn evaluate(&self, _board: &Board, score: i32) -> i32 {
+        -score
+    }
+}
+
+// captured pieces change sides and can be dropped onto any empty square as a move,
+// pawns not on the first or last rank
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn drops(&self) -> bool {
+        true
+    }
+
+    // a piece in hand is worth nearly as much as one on the board
+    fn evaluate(&self, board: &Board, score: i32) -> i32 {
+        const VALUES: [i32; Role::COUNT] = [0, 800, 300, 300, 450, 100, 700, 750, 1000, 220];
+        let color = board.side_to_move();
+        let in_hand = |color| -> i32 {
+            let pocket = board.pocket(color);
+            Role::ALL.iter().map(|&role| pocket[role as usize] as i32 * VALUES[role as usize]).sum()
+        };
+        score + in_hand(color) - in_hand(color.opposite())
+    }
+}
+
+// captures explode, and blowing up the enemy king wins
+pub struct Atomic;
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "Atomic"
+    }
+
+    fn explodes(&self) -> bool {
+        true
+    }
+
+    // a move may leave the king attacked if it blows up the enemy king, but never blow
+    // up its own king
+    fn filter_moves(&self, board: &mut Board, color: Color, moves: &mut Vec<Move>) {
+        moves.retain(|&mv| {
+            let is_king = board.piece_on(mv.from).is_some_and(|piece| piece.role == Role::King);
+            if is_king && board.is_capture(mv) {
+                return false;
+            }
+            let undo = board.make_move(mv);
+            let legal = board.pieces(color, Role::King) != 0
+                && (board.pieces(color.opposite(), Role::King) == 0 || !board.is_king_in_check(color));
+            board.unmake_move(mv, undo);
+            legal
+        });
+    }
+
+    fn end(&self, board: &Board) -> Option<GameEnd> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| board.pieces(color, Role::King) == 0)
+            .map(|color| GameEnd { winner: Some(color.opposite()), reason: "king exploded" })
+    }
+}
+
+// 10x8, with an archbishop and a chancellor beside the bishops
+pub struct Capablanca;
+
+impl Variant for Capablanca {
+    fn name(&self) -> &'static str {
+        "Capablanca"
+    }
+
+    fn start(&self) -> (usize, &'static [Role]) {
+        let back_rank = &[
+            Role::Rook,
+            Role::Knight,
+            Role::Archbishop,
+            Role::Bishop,
+            Role::Queen,
+            Role::King,
+            Role::Bishop,
+            Role::Chancellor,
+            Role::Knight,
+            Role::Rook,
+        ];
+        (8, back_rank)
+    }
+
+    fn promotions(&self) -> &'static [Role] {
+        &[Role::Queen, Role::Chancellor, Role::Archbishop, Role::Rook, Role::Bishop, Role::Knight]
+    }
+}
+
+// Gardner's 5x5 minichess: one of each piece, pawns only step once and no castling
+pub struct Gardner;
+
+impl Variant for Gardner {
+    fn name(&self) -> &'static str {
+        "Gardner minichess"
+    }
+
+    fn start(&self) -> (usize, &'static [Role]) {
+        (5, &[Role::Rook, Role::Knight, Role::Bishop, Role::Queen, Role::King])
+    }
+
+    fn castling(&self) -> bool {
+        false
+    }
+}
+
+pub const ALL: [&dyn Variant; 8] = [
+    &Standard,
+    &KingOfTheHill,
+    &ThreeCheck,
+    &Antichess,
+    &Crazyhouse,
+    &Atomic,
+    &Capablanca,
+    &Gardner,
+];
+
+pub fn from_name(name: &str) -> Option<&'static dyn Variant> {
+    ALL.into_iter().find(|variant| variant.name() == name)
+}
+
+// the variant after `variant` in `ALL`, for cycling through them on a button
+pub fn next(variant: &dyn Variant) -> &'static dyn Variant {
+    let index = ALL.iter().position(|other| other.name() == variant.name()).map_or(0, |index| index + 1);
+    ALL[index % ALL.len()]
+}
@@ -0,0 +1,133 @@
+use crate::bitboard::{MAX_WIDTH, SQUARES, Square};
+use crate::piece::{Color, Piece, Role};
+
+// a position key is the xor of one random number per feature of the position, so
+// making a move only has to xor out what changed and xor in what replaced it
+pub type Hash = u64;
+
+// splitmix64, good enough to fill the tables at compile time with a fixed seed
+const fn next_random(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+struct Keys {
+    pieces: [[[Hash; SQUARES]; Role::COUNT]; 2],
+    black_to_move: Hash,
+    castling: [Hash; 4],
+    en_passant_file: [Hash; MAX_WIDTH],
+    checks: [[Hash; 3]; 2],
+    pockets: [[[Hash; 16]; Role::COUNT]; 2],
+}
+
+const fn keys() -> Keys {
+    let mut keys = Keys {
+        pieces: [[[0; SQUARES]; Role::COUNT]; 2],
+        black_to_move: 0,
+        castling: [0; 4],
+        en_passant_file: [0; MAX_WIDTH],
+        checks: [[0; 3]; 2],
+        pockets: [[[0; 16]; Role::COUNT]; 2],
+    };
+    let mut state = 0x5eed;
+    let mut color = 0;
+    while color < 2 {
+        let mut role = 0;
+        while role < Role::COUNT {
+            let mut sq = 0;
+            while sq < SQUARES {
+                let (next, key) = next_random(state);
+                state = next;
+                keys.pieces[color][role][sq] = key;
+                sq += 1;
+            }
+            role += 1;
+        }
+        color += 1;
+    }
+    let (next, key) = next_random(state);
+    state = next;
+    keys.black_to_move = key;
+    let mut i = 0;
+    while i < 4 {
+        let (next, key) = next_random(state);
+        state = next;
+        keys.castling[i] = key;
+        i += 1;
+    }
+    let mut file = 0;
+    while file < MAX_WIDTH {
+        let (next, key) = next_random(state);
+        state = next;
+        keys.en_passant_file[file] = key;
+        file += 1;
+    }
+    let mut color = 0;
+    while color < 2 {
+        let mut count = 0;
+        while count < 3 {
+            let (next, key) = next_random(state);
+            state = next;
+            keys.checks[color][count] = key;
+            count += 1;
+        }
+        color += 1;
+    }
+    let mut color = 0;
+    while color < 2 {
+        let mut role = 0;
+        while role < Role::COUNT {
+            let mut count = 0;
+            while count < 16 {
+                let (next, key) = next_random(state);
+                state = next;
+                keys.pockets[color][role][count] = key;
+                count += 1;
+            }
+            role += 1;
+        }
+        color += 1;
+    }
+    keys
+}
+
+const KEYS: Keys = keys();
+
+pub fn piece_key(piece: Piece, sq: Square) -> Hash {
+    KEYS.pieces[piece.color as usize][piece.role as usize][sq]
+}
+
+pub fn side_key(color: Color) -> Hash {
+    match color {
+        Color::White => 0,
+        Color::Black => KEYS.black_to_move,
+    }
+}
+
+// rights are ordered white king side, white queen side, black king side, black queen side
+pub fn castling_key(rights: [bool; 4]) -> Hash {
+    (0..4).filter(|&i| rights[i]).fold(0, |key, i| key ^ KEYS.castling[i])
+}
+
+pub fn en_passant_key(file: Option<usize>) -> Hash {
+    file.map_or(0, |file| KEYS.en_passant_file[file])
+}
+
+// for variants that count checks, no checks given adds nothing
+pub fn checks_key(color: Color, checks: u8) -> Hash {
+    match checks {
+        0 => 0,
+        _ => KEYS.checks[color as usize][(checks as usize - 1).min(2)],
+    }
+}
+
+// one key per count of a piece in hand, an empty pocket adds nothing
+pub fn pocket_key(color: Color, role: Role, count: u8) -> Hash {
+    match count {
+        0 => 0,
+        _ => KEYS.pockets[color as usize][role as usize][(count as usize - 1).min(15)],
+    }
+}
@@ -0,0 +1,67 @@
+// move generation checked against the published perft counts, standard positions first and
+// then a start position for each variant that changes how pieces move or what is legal
+use chess::notation;
+use chess::variant;
+
+fn perft(variant: &str, fen: &str, depth: u32) -> u64 {
+    let variant = variant::from_name(variant).expect("a known variant");
+    let mut board = notation::parse_fen(fen, variant).expect("a valid FEN");
+    let color = board.side_to_move();
+    board.perft(color, depth)
+}
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[test]
+fn standard_start() {
+    assert_eq!(perft("Standard", START, 4), 197_281);
+}
+
+#[test]
+fn kiwipete() {
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    assert_eq!(perft("Standard", fen, 3), 97_862);
+}
+
+#[test]
+fn position_3() {
+    assert_eq!(perft("Standard", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4), 43_238);
+}
+
+#[test]
+fn position_4() {
+    let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    assert_eq!(perft("Standard", fen, 3), 9_467);
+}
+
+#[test]
+fn position_5() {
+    let fen = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    assert_eq!(perft("Standard", fen, 3), 62_379);
+}
+
+#[test]
+fn chess960() {
+    let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+    assert_eq!(perft("Standard", fen, 3), 12_189);
+}
+
+#[test]
+fn atomic_start() {
+    assert_eq!(perft("Atomic", START, 4), 197_326);
+}
+
+#[test]
+fn antichess_start() {
+    assert_eq!(perft("Antichess", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1", 4), 153_299);
+}
+
+#[test]
+fn crazyhouse_start() {
+    assert_eq!(perft("Crazyhouse", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1", 4), 197_281);
+}
+
+#[test]
+fn three_check_start() {
+    assert_eq!(perft("Three-check", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 +0+0", 4), 197_281);
+}